@@ -0,0 +1,339 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+const EXIT_CODES_HELP: &str = "\
+結束代碼:
+  0  成功
+  1  發現警告 (字幕異常、設定警告)，成果仍已輸出
+  2  參數或設定錯誤
+  3  轉換失敗 (--strict 時警告亦歸為此類)
+  4  I/O 錯誤 (讀寫檔案、下載、通知發送)";
+
+/// 程序結束代碼，數字越大越嚴重；批次處理取最嚴重者。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Success = 0,
+    Warnings = 1,
+    BadArgs = 2,
+    ConvertError = 3,
+    IoError = 4,
+}
+
+impl Outcome {
+    pub fn merge(self, other: Outcome) -> Outcome {
+        self.max(other)
+    }
+
+    /// --strict：警告視為失敗
+    pub fn strict(self, strict: bool) -> Outcome {
+        if strict && self == Outcome::Warnings {
+            Outcome::ConvertError
+        } else {
+            self
+        }
+    }
+
+    /// 依錯誤種類區分參數錯誤、轉換失敗與 I/O 錯誤。
+    pub fn from_error(e: &cw::Error) -> Outcome {
+        use std::io::ErrorKind;
+        match e {
+            cw::Error::Io { source, .. } => match source.kind() {
+                ErrorKind::InvalidData | ErrorKind::InvalidInput => Outcome::ConvertError,
+                _ => Outcome::IoError,
+            },
+            cw::Error::Network(_) => Outcome::IoError,
+            cw::Error::Encoding(_) | cw::Error::Parse { .. } | cw::Error::OpenCc { .. } => {
+                Outcome::ConvertError
+            }
+            cw::Error::Config(_) => Outcome::BadArgs,
+        }
+    }
+}
+
+impl From<Outcome> for std::process::ExitCode {
+    fn from(o: Outcome) -> Self {
+        std::process::ExitCode::from(o as u8)
+    }
+}
+
+/// CW 字幕工作站：OpenCC 繁簡轉換、診斷與通知。
+///
+/// 未指定子命令時等同 `cw convert`；無檔案且 stdin 為管道時進入管道模式。
+#[derive(Parser, Debug)]
+#[command(
+    name = "cw",
+    version,
+    args_conflicts_with_subcommands = true,
+    after_help = EXIT_CODES_HELP
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub convert: ConvertArgs,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct GlobalArgs {
+    /// 專業模式 (S2TWP，台灣慣用詞)
+    #[arg(short = 'p', long = "phrase", global = true)]
+    pub phrase: bool,
+
+    /// 套用 cw.cfg 中的 [profile.名稱] 區段
+    #[arg(long, value_name = "名稱", global = true)]
+    pub profile: Option<String>,
+
+    /// 臨時覆寫設定，可重複使用
+    #[arg(long = "set", value_name = "鍵=值", value_parser = parse_key_val, global = true)]
+    pub set: Vec<(String, String)>,
+
+    /// 嚴格模式：有任何警告即以失敗代碼結束
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// 字幕格式，auto 依內容判斷 (預設取自設定 format)
+    #[arg(long, value_name = "格式", value_parser = ["auto", "srt", "ass", "vtt", "microdvd", "sbv", "smi", "ttml", "lrc", "plain", "markdown"], global = true)]
+    pub format: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 轉換字幕檔 (預設命令)
+    Convert(Box<ConvertArgs>),
+    /// 只掃描不轉換、不寫檔，列出問題
+    Check(FilesArgs),
+    /// 修正檔尾缺少的 SRT 規範空行 (直接寫回原檔)
+    Fix(FilesArgs),
+    /// 對比原檔與譯檔
+    Compare(CompareArgs),
+    /// 將既有成果檔發送至 Discord / 郵件
+    Notify(NotifyArgs),
+    /// 檢視或驗證設定
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// 生成預設 cw.cfg 於執行檔旁
+    Init,
+    /// 輸出 shell 補全腳本
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct ConvertArgs {
+    /// 要轉換的字幕檔、目錄或萬用字元
+    #[arg(value_name = "檔案")]
+    pub files: Vec<String>,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// 覆寫原檔 (影子檔案，轉換完成才取代)
+    #[arg(short = 'd', long = "overwrite", conflicts_with_all = ["output_dir", "name"])]
+    pub overwrite: bool,
+
+    /// 輸出目錄，掃描目錄時保留子目錄結構
+    #[arg(short = 'o', long = "output-dir", value_name = "目錄")]
+    pub output_dir: Option<String>,
+
+    /// 同時處理的檔案數，0 表示依 CPU 核心數 (預設取自設定 jobs)
+    #[arg(short = 'j', long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// 輸出換行：auto 沿用輸入 (預設取自設定 line_ending)
+    #[arg(long, value_name = "auto|lf|crlf", value_parser = ["auto", "lf", "crlf"])]
+    pub eol: Option<String>,
+
+    /// 輸出 BOM：auto 沿用輸入 (預設取自設定 bom)
+    #[arg(long, value_name = "auto|on|off", value_parser = ["auto", "on", "off"])]
+    pub bom: Option<String>,
+
+    /// 輸入編碼 (例如 gbk、big5、utf-16le)，auto 為自動偵測
+    #[arg(long = "input-encoding", value_name = "編碼")]
+    pub input_encoding: Option<String>,
+
+    /// 輸出編碼：utf-8 / gbk / big5 / utf-16le / utf-16be
+    #[arg(long = "output-encoding", value_name = "編碼", value_parser = ["utf-8", "gbk", "big5", "utf-16le", "utf-16be"])]
+    pub output_encoding: Option<String>,
+
+    /// 輸出格式：same 沿用輸入，或 srt / microdvd / sbv / smi / ttml (預設取自設定 output_format)；
+    /// 輸出檔名改用目標格式副檔名
+    #[arg(long, value_name = "格式", value_parser = ["same", "srt", "microdvd", "sbv", "smi", "ttml"])]
+    pub to: Option<String>,
+
+    /// MicroDVD 影格率，auto 取檔頭 {1}{1}fps 或 23.976 (預設取自設定 frame_rate)
+    #[arg(long, value_name = "fps")]
+    pub fps: Option<String>,
+
+    /// 嘗試修復重複解碼造成的亂碼 (例如 UTF-8 被當成 GBK)
+    #[arg(long = "repair-mojibake")]
+    pub repair_mojibake: bool,
+
+    /// LRC 歌詞一併轉換 [ti:] [ar:] [al:] 等標籤 (預設取自設定 lrc_metadata)
+    #[arg(long = "lrc-metadata")]
+    pub lrc_metadata: bool,
+
+    /// 輸出檔名模板，可用 {name} {stem} {ext} {profile} {conversion}
+    #[arg(long, value_name = "模板")]
+    pub name: Option<String>,
+
+    /// 從 MEGA 連結下載字幕後轉換
+    #[arg(long, value_name = "URL")]
+    pub task: Option<String>,
+
+    /// 舊版對比旗標，等同 `cw compare A B`
+    #[arg(short = 'a', hide = true)]
+    pub legacy_compare: bool,
+
+    /// 舊版初始化旗標，等同 `cw init`
+    #[arg(long = "init", hide = true)]
+    pub legacy_init: bool,
+
+    #[command(flatten)]
+    pub notify: NotifyOptions,
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct NotifyOptions {
+    /// 完成後傳送至 Discord
+    #[arg(short = 'b', long = "discord")]
+    pub discord: bool,
+
+    /// 完成後寄送郵件
+    #[arg(short = 'm', long = "mail")]
+    pub mail: bool,
+
+    /// 發到既有 Discord 討論串
+    #[arg(long, value_name = "ID")]
+    pub thread: Option<String>,
+
+    /// 在論壇頻道以模板開新貼文
+    #[arg(long = "thread-name", value_name = "模板")]
+    pub thread_name: Option<String>,
+
+    /// Discord 訊息開場白
+    #[arg(long, value_name = "文字")]
+    pub intro: Option<String>,
+
+    /// Discord 訊息模板檔
+    #[arg(long, value_name = "檔案")]
+    pub template: Option<String>,
+}
+
+/// 目錄與萬用字元展開選項；展開時略過 cw 的成果檔 (`*.srt.txt`、`*.tmp`)，明確指定的檔案一律處理。
+#[derive(Args, Debug, Default, Clone)]
+pub struct InputArgs {
+    /// 掃描目錄時包含子目錄
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// 只收錄符合的檔案 (比對相對路徑或檔名)，可重複使用
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// 排除符合的檔案，可重複使用
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// 掃描目錄時收錄的副檔名，預設取自設定 input_extensions
+    #[arg(long, value_name = "srt,ass,...")]
+    pub ext: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct FilesArgs {
+    /// 字幕檔、目錄或萬用字元
+    #[arg(value_name = "檔案", required = true)]
+    pub files: Vec<String>,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// 原檔
+    pub a: String,
+    /// 譯檔
+    pub b: String,
+}
+
+#[derive(Args, Debug)]
+pub struct NotifyArgs {
+    /// 要發送的成果檔
+    #[arg(value_name = "檔案", required = true)]
+    pub files: Vec<String>,
+
+    #[command(flatten)]
+    pub notify: NotifyOptions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// 顯示生效設定與來源
+    Show {
+        /// 以此檔案所在目錄尋找專案 cw.cfg
+        file: Option<String>,
+    },
+    /// 驗證設定檔
+    Check { file: Option<String> },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .ok_or_else(|| format!("格式應為 鍵=值：`{}`", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn merge_keeps_most_severe() {
+        assert_eq!(Outcome::Success.merge(Outcome::Warnings), Outcome::Warnings);
+        assert_eq!(Outcome::IoError.merge(Outcome::BadArgs), Outcome::IoError);
+        assert_eq!(Outcome::Warnings.strict(true), Outcome::ConvertError);
+        assert_eq!(Outcome::Warnings.strict(false), Outcome::Warnings);
+        assert_eq!(Outcome::Success.strict(true), Outcome::Success);
+    }
+
+    #[test]
+    fn errors_map_to_documented_exit_codes() {
+        let io = |kind| cw::Error::io_at("a.srt", io::Error::new(kind, "x"));
+        let cases = [
+            (io(io::ErrorKind::NotFound), 4),
+            (io(io::ErrorKind::InvalidData), 3),
+            (cw::Error::Network("timeout".into()), 4),
+            (cw::Error::Encoding("gbk".into()), 3),
+            (cw::Error::parse(1, 2, "x"), 3),
+            (cw::Error::Config(Vec::new()), 2),
+        ];
+        for (e, code) in cases {
+            assert_eq!(Outcome::from_error(&e) as u8, code, "{}", e);
+        }
+    }
+
+    #[test]
+    fn parse_key_val_rejects_missing_key() {
+        assert_eq!(
+            parse_key_val(" jobs = 4 "),
+            Ok(("jobs".to_string(), "4".to_string()))
+        );
+        assert!(parse_key_val("=4").is_err());
+        assert!(parse_key_val("jobs").is_err());
+    }
+}
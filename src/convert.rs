@@ -0,0 +1,204 @@
+// ==========================================
+// CW In-memory Conversion
+// ==========================================
+//
+// 給函式庫使用者的記憶體內轉換：字串進、字串出，不碰檔案系統。
+// 格式判斷、ASS 區段追蹤與診斷都在這裡處理，呼叫端不必自行建立 OpenCC / RawGuard。
+
+use crate::core::{self, Collect, Converter, StreamOptions};
+use crate::encoding::OutputEncoding;
+use crate::error::Result;
+use crate::format::Format;
+use crate::mojibake::Chain;
+use crate::report_format::SubtitleIssue;
+use opencc_rust::DefaultConfig;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConvertOptions {
+    /// OpenCC 轉換設定，預設 `S2T`
+    pub conversion: DefaultConfig,
+    /// SRT 缺少檔尾規範空行時補上
+    pub fix_trailing_newline: bool,
+    /// 轉換前嘗試反轉重複解碼的亂碼行
+    pub repair_mojibake: bool,
+    /// 輸出格式 (SRT / MicroDVD / SBV / SAMI / TTML 互轉)；`None` 沿用輸入格式
+    pub output_format: Option<Format>,
+    /// MicroDVD 影格率；`None` 取檔頭或 `subtitle::DEFAULT_FPS`
+    pub frame_rate: Option<f64>,
+    /// LRC 一併轉換 `[ti:]`、`[ar:]` 等標籤的值
+    pub lrc_metadata: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            conversion: DefaultConfig::S2T,
+            fix_trailing_newline: false,
+            repair_mojibake: false,
+            output_format: None,
+            frame_rate: None,
+            lrc_metadata: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConversionResult {
+    /// 轉換後的全文 (指定 `output_format` 時為該格式)；換行風格沿用輸入，每行皆以換行結尾
+    pub text: String,
+    /// 輸入的格式 (指定或偵測所得)
+    pub format: Format,
+    /// 每一行的 (行號, 原文, 譯文)
+    pub pairs: Vec<(usize, String, String)>,
+    pub changed_lines: usize,
+    /// 輸入的診斷結果；已修復的亂碼行不列入
+    pub issues: Vec<SubtitleIssue>,
+    /// 已修復的亂碼行 (行號, 反轉的編碼鏈)
+    pub repaired: Vec<(usize, Vec<Chain>)>,
+}
+
+/// 轉換一段字幕文字；`format` 為 `None` 時依內容判斷。
+/// 每次呼叫都會載入 OpenCC，大量呼叫請改用 `convert_str_with` 共用 `Converter`。
+pub fn convert_str(
+    text: &str,
+    format: Option<Format>,
+    options: &ConvertOptions,
+) -> Result<ConversionResult> {
+    let converter = Converter::new(options.conversion)?;
+    convert_str_with(&converter, text, format, options)
+}
+
+/// 同 `convert_str`，使用既有的 `Converter` (`options.conversion` 不使用)。
+pub fn convert_str_with(
+    converter: &Converter,
+    text: &str,
+    format: Option<Format>,
+    options: &ConvertOptions,
+) -> Result<ConversionResult> {
+    let format = format.unwrap_or_else(|| Format::detect(text));
+    let opts = StreamOptions {
+        fix_trailing_newline: options.fix_trailing_newline,
+        diagnose: true,
+        collect: Collect::All,
+        line_ending: core::LineEnding::Preserve,
+        bom: core::Bom::Never,
+        input_encoding: Some(encoding_rs::UTF_8),
+        output_encoding: OutputEncoding::Utf8,
+        repair_mojibake: options.repair_mojibake,
+        format: Some(format),
+        output_format: options.output_format,
+        frame_rate: options.frame_rate,
+        lrc_metadata: options.lrc_metadata,
+    };
+    let mut out = Vec::with_capacity(text.len() + 16);
+    let summary = converter.convert_stream(text.as_bytes(), &mut out, &opts)?;
+    Ok(ConversionResult {
+        // 輸入為合法 UTF-8 且輸出字集為 UTF-8，不會有無效位元組
+        text: String::from_utf8_lossy(&out).into_owned(),
+        format,
+        pairs: summary.pairs,
+        changed_lines: summary.changed,
+        issues: summary.issues,
+        repaired: summary.repaired,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:02,500\n软件\n\n";
+
+    fn convert(text: &str, format: Option<Format>) -> ConversionResult {
+        convert_str(text, format, &ConvertOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn format_is_detected_when_not_given() {
+        assert_eq!(convert(SRT, None).format, Format::Srt);
+        assert_eq!(convert("[Events]\n", None).format, Format::Ass);
+        assert_eq!(convert("WEBVTT\n\n", None).format, Format::WebVtt);
+        assert_eq!(convert("软件\n", None).format, Format::Plain);
+        // 指定格式時不再判斷
+        assert_eq!(convert(SRT, Some(Format::Plain)).format, Format::Plain);
+    }
+
+    #[test]
+    fn ass_styles_are_left_alone() {
+        let text = "[V4+ Styles]\n\
+                    Style: 软件,Arial,20\n\
+                    \n\
+                    [Events]\n\
+                    Dialogue: 0,0:00:01.00,0:00:02.00,软件,,0,0,0,,{\\i1}软件\n";
+        let result = convert(text, None);
+        assert_eq!(
+            result.text,
+            "[V4+ Styles]\n\
+             Style: 软件,Arial,20\n\
+             \n\
+             [Events]\n\
+             Dialogue: 0,0:00:01.00,0:00:02.00,软件,,0,0,0,,{\\i1}軟件\n"
+        );
+        assert_eq!(result.changed_lines, 1);
+    }
+
+    #[test]
+    fn pairs_and_issues_describe_the_input() {
+        let text = "1\n00:00:05,000 --> 00:00:02,000\n软件\n";
+        let result = convert(text, None);
+        assert_eq!(
+            result.pairs,
+            vec![
+                (1, "1".to_string(), "1".to_string()),
+                (
+                    2,
+                    "00:00:05,000 --> 00:00:02,000".to_string(),
+                    "00:00:05,000 --> 00:00:02,000".to_string()
+                ),
+                (3, "软件".to_string(), "軟件".to_string()),
+            ]
+        );
+        assert_eq!(result.changed_lines, 1);
+        let issues: Vec<_> = result
+            .issues
+            .iter()
+            .map(|i| (i.line, i.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (0, "檔案末端損壞：缺少 SRT 規範空行"),
+                (2, "時間邏輯錯誤：結束早於開始"),
+            ]
+        );
+        // 預設不補空行；指定後補上
+        assert_eq!(result.text, "1\n00:00:05,000 --> 00:00:02,000\n軟件\n");
+        let options = ConvertOptions {
+            fix_trailing_newline: true,
+            ..Default::default()
+        };
+        let fixed = convert_str(text, None, &options).unwrap();
+        assert!(fixed.text.ends_with("軟件\n\n"));
+    }
+
+    #[test]
+    fn output_format_rewrites_the_timeline() {
+        let options = ConvertOptions {
+            output_format: Some(Format::Sbv),
+            ..Default::default()
+        };
+        let result = convert_str(SRT, None, &options).unwrap();
+        assert_eq!(result.format, Format::Srt);
+        assert_eq!(result.text, "0:00:01.000,0:00:02.500\n軟件\n\n");
+    }
+
+    #[test]
+    fn text_is_never_read_as_a_path() {
+        // 看起來像存在的檔案路徑也只當作文字轉換
+        let result = convert("Cargo.toml\n", None);
+        assert_eq!(result.format, Format::Plain);
+        assert_eq!(result.text, "Cargo.toml\n");
+        let result = convert("src/软件.srt", None);
+        assert_eq!(result.text, "src/軟件.srt\n");
+    }
+}
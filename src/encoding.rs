@@ -1,2 +1,325 @@
+// ==========================================
+// CW Encoding Module
+// ==========================================
+//
+// 輸入編碼偵測 (含可信度)、使用者指定的輸入編碼、所有讀取路徑共用的解碼，
+// 以及輸出到 UTF-8 以外字集。
 
+use crate::error::{Error, Result};
+use chardetng::EncodingDetector;
+use encoding_rs::{DecoderResult, EncoderResult, Encoding, BIG5, GBK, UTF_16BE, UTF_16LE, UTF_8};
+use std::fs;
+use std::path::Path;
 
+/// 設定與命令列可用的輸出編碼名稱
+pub const OUTPUT_ENCODINGS: &[&str] = &["utf-8", "gbk", "big5", "utf-16le", "utf-16be"];
+
+/// 偵測可信度：BOM 或合法 UTF-8 為 `High`，chardetng 認為可靠為 `Medium`，其餘為 `Low`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    pub encoding: &'static Encoding,
+    pub confidence: Confidence,
+    /// 輸入以 BOM 開頭 (UTF-8 / UTF-16)
+    pub bom: bool,
+}
+
+/// 以檔案開頭判斷編碼；`sample` 不必是完整檔案。
+pub fn detect(sample: &[u8]) -> Detection {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return Detection {
+            encoding,
+            confidence: Confidence::High,
+            bom: true,
+        };
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, true);
+    let (encoding, reliable) = detector.guess_assess(None, true);
+    let confidence = if encoding == UTF_8 && !sample.is_ascii() {
+        Confidence::High
+    } else if reliable {
+        Confidence::Medium
+    } else {
+        Confidence::Low
+    };
+    Detection {
+        encoding,
+        confidence,
+        bom: false,
+    }
+}
+
+/// 解析 `--input-encoding` 的值；`auto` 或空字串回傳 `None` 表示自動偵測。
+/// 接受 WHATWG 標籤 (gbk、big5、shift_jis…) 以及 `utf-16` (視為 UTF-16LE)。
+pub fn lookup(label: &str) -> Result<Option<&'static Encoding>> {
+    let l = label.trim().to_lowercase();
+    match l.as_str() {
+        "" | "auto" => Ok(None),
+        "utf-16" | "utf16" => Ok(Some(UTF_16LE)),
+        _ => Encoding::for_label(l.as_bytes())
+            .map(Some)
+            .ok_or_else(|| Error::Encoding(format!("不支援的編碼: {}", label))),
+    }
+}
+
+/// 輸出字集。`Utf16Le` / `Utf16Be` 由本模組自行編碼 (encoding_rs 只解碼 UTF-16)。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputEncoding {
+    #[default]
+    Utf8,
+    Gbk,
+    Big5,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl OutputEncoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "gbk" => Some(Self::Gbk),
+            "big5" => Some(Self::Big5),
+            "utf-16le" | "utf-16" => Some(Self::Utf16Le),
+            "utf-16be" => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.encoding().name()
+    }
+
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            Self::Utf8 => UTF_8,
+            Self::Gbk => GBK,
+            Self::Big5 => BIG5,
+            Self::Utf16Le => UTF_16LE,
+            Self::Utf16Be => UTF_16BE,
+        }
+    }
+
+    /// MIME `charset` 參數 (IANA 名稱)，用於郵件附件等。
+    pub fn charset(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Gbk => "gbk",
+            Self::Big5 => "big5",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+        }
+    }
+
+    /// 此字集的 BOM；GBK / Big5 沒有 BOM。
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => b"\xEF\xBB\xBF",
+            Self::Utf16Le => b"\xFF\xFE",
+            Self::Utf16Be => b"\xFE\xFF",
+            Self::Gbk | Self::Big5 => b"",
+        }
+    }
+}
+
+/// 目標字集無法表示的字元會以此取代
+pub const REPLACEMENT: char = '?';
+
+/// 將 UTF-8 文字逐段編碼為目標字集。
+pub struct TextEncoder {
+    target: OutputEncoding,
+    encoder: Option<encoding_rs::Encoder>,
+}
+
+impl TextEncoder {
+    pub fn new(target: OutputEncoding) -> Self {
+        let encoder = match target {
+            OutputEncoding::Gbk | OutputEncoding::Big5 => Some(target.encoding().new_encoder()),
+            _ => None,
+        };
+        Self { target, encoder }
+    }
+
+    pub fn target(&self) -> OutputEncoding {
+        self.target
+    }
+
+    /// 編碼 `text` 並附加到 `out`；無法表示的字元以 `REPLACEMENT` 取代並回報給 `unmappable`。
+    pub fn encode(&mut self, text: &str, out: &mut Vec<u8>, mut unmappable: impl FnMut(char)) {
+        match self.target {
+            OutputEncoding::Utf8 => out.extend_from_slice(text.as_bytes()),
+            OutputEncoding::Utf16Le => text
+                .encode_utf16()
+                .for_each(|u| out.extend_from_slice(&u.to_le_bytes())),
+            OutputEncoding::Utf16Be => text
+                .encode_utf16()
+                .for_each(|u| out.extend_from_slice(&u.to_be_bytes())),
+            OutputEncoding::Gbk | OutputEncoding::Big5 => {
+                let Some(enc) = self.encoder.as_mut() else {
+                    return;
+                };
+                let mut src = text;
+                loop {
+                    out.reserve(
+                        enc.max_buffer_length_from_utf8_without_replacement(src.len())
+                            .unwrap_or(src.len() * 4),
+                    );
+                    let (res, read) =
+                        enc.encode_from_utf8_to_vec_without_replacement(src, out, false);
+                    src = &src[read..];
+                    match res {
+                        EncoderResult::InputEmpty => break,
+                        EncoderResult::OutputFull => continue,
+                        EncoderResult::Unmappable(c) => {
+                            unmappable(c);
+                            out.push(REPLACEMENT as u8);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 解碼後的文字與偵測資訊。
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub text: String,
+    /// 實際用於解碼的編碼 (指定編碼時可信度為 `High`)
+    pub detection: Detection,
+    /// 含有不符合該編碼位元組的行號 (已以 U+FFFD 取代)
+    pub malformed_lines: Vec<usize>,
+}
+
+/// 所有讀取路徑共用的解碼：BOM 優先，其次為指定編碼，否則自動偵測。
+pub fn decode(raw: &[u8], forced: Option<&'static Encoding>) -> Decoded {
+    let detected = detect(raw);
+    let detection = match forced {
+        Some(encoding) if !detected.bom => Detection {
+            encoding,
+            confidence: Confidence::High,
+            bom: false,
+        },
+        _ => detected,
+    };
+    let mut decoder = detection.encoding.new_decoder_with_bom_removal();
+    let mut text = String::new();
+    let mut malformed_lines = Vec::new();
+    let (mut line, mut counted) = (1, 0);
+    let mut src = raw;
+    loop {
+        text.reserve(
+            decoder
+                .max_utf8_buffer_length_without_replacement(src.len())
+                .unwrap_or(src.len() * 3 + 16),
+        );
+        let (res, read) = decoder.decode_to_string_without_replacement(src, &mut text, true);
+        src = &src[read..];
+        match res {
+            DecoderResult::InputEmpty => break,
+            DecoderResult::OutputFull => continue,
+            DecoderResult::Malformed(_, _) => {
+                line += text[counted..].matches('\n').count();
+                counted = text.len();
+                if malformed_lines.last() != Some(&line) {
+                    malformed_lines.push(line);
+                }
+                text.push('\u{FFFD}');
+            }
+        }
+    }
+    Decoded {
+        text,
+        detection,
+        malformed_lines,
+    }
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P, forced: Option<&'static Encoding>) -> Result<Decoded> {
+    let path = path.as_ref();
+    let raw = fs::read(path).map_err(|e| Error::io_at(path, e))?;
+    Ok(decode(&raw, forced))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{BIG5, GBK, UTF_16BE};
+
+    fn encode(text: &str, target: OutputEncoding) -> (Vec<u8>, Vec<char>) {
+        let mut out = Vec::new();
+        let mut lost = Vec::new();
+        TextEncoder::new(target).encode(text, &mut out, |c| lost.push(c));
+        (out, lost)
+    }
+
+    #[test]
+    fn bom_wins_over_forced_encoding() {
+        let raw = [b"\xEF\xBB\xBF".as_slice(), "軟體".as_bytes()].concat();
+        let d = decode(&raw, Some(GBK));
+        assert_eq!(d.text, "軟體");
+        assert_eq!(d.detection.encoding, UTF_8);
+        assert!(d.detection.bom);
+    }
+
+    #[test]
+    fn forced_encoding_skips_detection() {
+        let (raw, _) = encode("软件", OutputEncoding::Gbk);
+        let d = decode(&raw, Some(GBK));
+        assert_eq!(d.text, "软件");
+        assert_eq!(d.detection.confidence, Confidence::High);
+        assert!(d.malformed_lines.is_empty());
+    }
+
+    #[test]
+    fn gbk_and_big5_round_trip_through_detection() {
+        let text = "這是一段用來測試編碼偵測的繁體中文字幕，內容需要足夠長才能判斷。\n";
+        let (raw, lost) = encode(&text.repeat(4), OutputEncoding::Big5);
+        assert!(lost.is_empty());
+        let d = decode(&raw, None);
+        assert_eq!(d.detection.encoding, BIG5);
+        assert_eq!(d.text, text.repeat(4));
+        let text = "这是一段用来测试编码侦测的简体中文字幕，内容需要足够长才能判断。\n";
+        let (raw, _) = encode(&text.repeat(4), OutputEncoding::Gbk);
+        assert_eq!(decode(&raw, None).detection.encoding, GBK);
+    }
+
+    #[test]
+    fn malformed_bytes_are_reported_by_line() {
+        let raw = b"ok\n\xFF\xFE bad\nok\n\xC3 bad\n";
+        let d = decode(raw, Some(UTF_8));
+        assert_eq!(d.malformed_lines, [2, 4]);
+        assert_eq!(d.text.matches('\u{FFFD}').count(), 3);
+    }
+
+    #[test]
+    fn utf16_output_and_decode() {
+        let (raw, _) = encode("软\n", OutputEncoding::Utf16Be);
+        assert_eq!(raw, [0x8F, 0x6F, 0x00, 0x0A]);
+        assert_eq!(decode(&raw, Some(UTF_16BE)).text, "软\n");
+        let (raw, _) = encode("软\n", OutputEncoding::Utf16Le);
+        let with_bom = [OutputEncoding::Utf16Le.bom(), raw.as_slice()].concat();
+        assert_eq!(decode(&with_bom, None).text, "软\n");
+    }
+
+    #[test]
+    fn unmappable_characters_are_replaced() {
+        let (raw, lost) = encode("軟😀", OutputEncoding::Big5);
+        assert_eq!(lost, ['😀']);
+        assert_eq!(raw.last(), Some(&(REPLACEMENT as u8)));
+    }
+
+    #[test]
+    fn lookup_labels() {
+        assert_eq!(lookup("auto").unwrap(), None);
+        assert_eq!(lookup(" GBK ").unwrap(), Some(GBK));
+        assert_eq!(lookup("utf-16").unwrap(), Some(UTF_16LE));
+        assert!(matches!(lookup("klingon"), Err(Error::Encoding(_))));
+    }
+}
@@ -0,0 +1,166 @@
+// ==========================================
+// CW Error Type
+// ==========================================
+//
+// 函式庫對外的統一錯誤型別；所有公開 API 以 `cw::Result` 回傳，不因輸入內容 panic。
+
+use crate::core::ConfigDiagnostic;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// 讀寫失敗；`path` 為出錯的檔案 (串流等無路徑時為 `None`)
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// 不支援或無法使用的編碼
+    Encoding(String),
+    /// 字幕或設定內容格式錯誤；行、欄皆 1 起算，未知時為 0
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// OpenCC 無法載入指定的轉換設定
+    OpenCc { config: String, message: String },
+    /// 下載或外部服務失敗
+    Network(String),
+    /// 設定檔含有錯誤等級的問題
+    Config(Vec<ConfigDiagnostic>),
+}
+
+/// 預設錯誤型別為 `cw::Error`；第二個參數保留給少數回傳其他錯誤的內部函式。
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn io_at<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Error::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    pub fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Error::Parse {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io {
+                path: Some(p),
+                source,
+            } => write!(f, "{}: {}", p.display(), source),
+            Error::Io { path: None, source } => write!(f, "{}", source),
+            Error::Encoding(m) => write!(f, "編碼錯誤: {}", m),
+            Error::Parse {
+                line: 0, message, ..
+            } => write!(f, "格式錯誤: {}", message),
+            Error::Parse {
+                line,
+                column: 0,
+                message,
+            } => write!(f, "格式錯誤 (第 {} 行): {}", line, message),
+            Error::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "格式錯誤 (第 {} 行第 {} 欄): {}", line, column, message),
+            Error::OpenCc { config, message } => {
+                write!(f, "OpenCC 初始化失敗 ({}): {}", config, message)
+            }
+            Error::Network(m) => write!(f, "網路錯誤: {}", m),
+            Error::Config(diags) => {
+                write!(f, "設定錯誤")?;
+                for d in diags {
+                    write!(f, "\n  {}", d)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ConfigErrorKind, ConfigSource};
+    use std::error::Error as _;
+
+    #[test]
+    fn display_describes_each_kind() {
+        let cases = [
+            (Error::Encoding("xyz".into()), "編碼錯誤: xyz"),
+            (Error::parse(0, 0, "空檔"), "格式錯誤: 空檔"),
+            (
+                Error::parse(3, 0, "缺少時間軸"),
+                "格式錯誤 (第 3 行): 缺少時間軸",
+            ),
+            (
+                Error::parse(3, 17, "毫秒"),
+                "格式錯誤 (第 3 行第 17 欄): 毫秒",
+            ),
+            (
+                Error::OpenCc {
+                    config: "s2t.json".into(),
+                    message: "not found".into(),
+                },
+                "OpenCC 初始化失敗 (s2t.json): not found",
+            ),
+            (Error::Network("timeout".into()), "網路錯誤: timeout"),
+        ];
+        for (e, want) in cases {
+            assert_eq!(e.to_string(), want);
+        }
+        let diag = ConfigDiagnostic {
+            source: ConfigSource::Cli,
+            line: 0,
+            kind: ConfigErrorKind::UnknownProfile("hk".into()),
+        };
+        assert_eq!(
+            Error::Config(vec![diag]).to_string(),
+            "設定錯誤\n  命令列: 找不到 profile `hk`"
+        );
+    }
+
+    #[test]
+    fn io_errors_keep_the_path() {
+        let e = Error::io_at(
+            "dir/a.srt",
+            io::Error::new(io::ErrorKind::NotFound, "找不到"),
+        );
+        assert!(matches!(&e, Error::Io { path: Some(p), .. } if p == &PathBuf::from("dir/a.srt")));
+        assert_eq!(e.to_string(), "dir/a.srt: 找不到");
+        assert_eq!(e.source().unwrap().to_string(), "找不到");
+        // `?` 轉換的 I/O 錯誤沒有路徑
+        let e: Error = io::Error::new(io::ErrorKind::PermissionDenied, "拒絕").into();
+        assert!(
+            matches!(&e, Error::Io { path: None, source } if source.kind() == io::ErrorKind::PermissionDenied)
+        );
+        assert_eq!(e.to_string(), "拒絕");
+        assert!(Error::Network("x".into()).source().is_none());
+    }
+}
@@ -0,0 +1,256 @@
+// ==========================================
+// CW Format Module
+// ==========================================
+//
+// 字幕格式：決定轉換時要追蹤哪些區段，以及診斷要套用哪些規則。
+// 下載來的檔案副檔名常常不可靠 (例如 `test1.srt.txt.1`)，以內容判斷為主、副檔名為輔。
+
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Srt,
+    /// ASS / SSA：只轉換 `[Events]` 內的對白文字
+    Ass,
+    WebVtt,
+    /// `{起始影格}{結束影格}文字`
+    MicroDvd,
+    /// YouTube SubViewer：`0:00:01.000,0:00:02.000`
+    Sbv,
+    /// SAMI (.smi)：`<SYNC Start=毫秒>` 標記的 HTML
+    Sami,
+    /// TTML / DFXP / IMSC1 文字字幕 (XML)
+    Ttml,
+    /// 歌詞：`[mm:ss.xx]文字` 與 `[ti:標題]` 等標籤
+    Lrc,
+    /// 沒有字幕結構的純文字：整行轉換，不套用字幕結構判斷與標籤保護
+    Plain,
+    /// Markdown 文件：保留程式碼、網址與連結目標，只依副檔名或指定判斷
+    Markdown,
+}
+
+/// 設定與命令列可用的格式名稱；`auto` 表示依內容判斷。
+pub const FORMAT_NAMES: &[&str] = &[
+    "auto", "srt", "ass", "vtt", "microdvd", "sbv", "smi", "ttml", "lrc", "plain", "markdown",
+];
+
+/// 判斷時最多看幾行非空白行
+const SNIFF_LINES: usize = 200;
+
+static SRT_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d{1,2}:\d{2}:\d{2}[,.]\d{1,3}\s*-->\s*\d{1,2}:\d{2}:\d{2}[,.]\d{1,3}")
+        .expect("內建樣式")
+});
+static SBV_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d{1,2}:\d{2}:\d{2}\.\d{3},\d{1,2}:\d{2}:\d{2}\.\d{3}$").expect("內建樣式")
+});
+static MICRODVD_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\{\d+\}\{\d*\}").expect("內建樣式"));
+static LRC_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\[(\d{1,3}:\d{2}([.:]\d{1,3})?|(ti|ar|al|au|by|offset|length|re|ve|#):[^\]]*)\]")
+        .expect("內建樣式")
+});
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Srt => "srt",
+            Format::Ass => "ass",
+            Format::WebVtt => "vtt",
+            Format::MicroDvd => "microdvd",
+            Format::Sbv => "sbv",
+            Format::Sami => "smi",
+            Format::Ttml => "ttml",
+            Format::Lrc => "lrc",
+            Format::Plain => "plain",
+            Format::Markdown => "markdown",
+        }
+    }
+
+    /// 輸出檔的副檔名 (不含點)
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::MicroDvd => "sub",
+            Format::Plain => "txt",
+            Format::Markdown => "md",
+            f => f.name(),
+        }
+    }
+
+    /// 解析 `--format` 的值；`auto` 回傳 `None`。
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.trim().to_lowercase().as_str() {
+            "srt" => Some(Format::Srt),
+            "ass" | "ssa" => Some(Format::Ass),
+            "vtt" | "webvtt" => Some(Format::WebVtt),
+            "microdvd" | "sub" => Some(Format::MicroDvd),
+            "sbv" => Some(Format::Sbv),
+            "smi" | "sami" => Some(Format::Sami),
+            "ttml" | "dfxp" | "imsc" => Some(Format::Ttml),
+            "lrc" => Some(Format::Lrc),
+            "plain" | "txt" => Some(Format::Plain),
+            "markdown" | "md" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+
+    /// 依副檔名判斷；`.txt` 與未知副檔名回傳 `None`。
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        let ext = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "txt" | "plain" => None,
+            e => Format::from_name(e),
+        }
+    }
+
+    /// 依內容判斷；看不出任何字幕結構時回傳 `None`。
+    ///
+    /// 檔頭 (WEBVTT、ASS 區段、SAMI、TTML 根元素) 直接決定；其餘依開頭數行中各格式時間軸出現的次數。
+    /// WebVTT 規範要求檔頭，沒有檔頭的 `-->` 時間軸一律視為 SRT。
+    pub fn sniff(text: &str) -> Option<Format> {
+        let mut lines = text
+            .lines()
+            .map(|l| l.trim_start_matches('\u{feff}').trim())
+            .filter(|l| !l.is_empty())
+            .take(SNIFF_LINES)
+            .peekable();
+        if lines.peek().is_some_and(|l| l.starts_with("WEBVTT")) {
+            return Some(Format::WebVtt);
+        }
+        // 依序：SRT、SBV、MicroDVD、LRC；同票時取前者
+        let mut hits = [0usize; 4];
+        for l in lines {
+            if l.eq_ignore_ascii_case("[Script Info]")
+                || l.eq_ignore_ascii_case("[Events]")
+                || l.eq_ignore_ascii_case("[V4+ Styles]")
+                || l.eq_ignore_ascii_case("[V4 Styles]")
+            {
+                return Some(Format::Ass);
+            }
+            if l.get(..5).is_some_and(|h| h.eq_ignore_ascii_case("<sami")) {
+                return Some(Format::Sami);
+            }
+            if l.starts_with("<tt ")
+                || l.starts_with("<tt>")
+                || l.starts_with("<tt:tt")
+                || l.contains("xmlns=\"http://www.w3.org/ns/ttml\"")
+            {
+                return Some(Format::Ttml);
+            }
+            if SRT_TIME.is_match(l) {
+                hits[0] += 1;
+            } else if SBV_TIME.is_match(l) {
+                hits[1] += 1;
+            } else if MICRODVD_LINE.is_match(l) {
+                hits[2] += 1;
+            } else if LRC_LINE.is_match(l) {
+                hits[3] += 1;
+            }
+        }
+        let formats = [Format::Srt, Format::Sbv, Format::MicroDvd, Format::Lrc];
+        let (best, &count) = hits
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, n)| (n, usize::MAX - i))?;
+        (count > 0).then_some(formats[best])
+    }
+
+    /// 依內容判斷，看不出結構時為純文字。
+    pub fn detect(text: &str) -> Format {
+        Format::sniff(text).unwrap_or(Format::Plain)
+    }
+
+    /// 內容優先，其次副檔名，都判斷不出時為純文字。
+    pub fn resolve<P: AsRef<Path>>(path: P, text: &str) -> Format {
+        Format::sniff(text)
+            .or_else(|| Format::from_path(path))
+            .unwrap_or(Format::Plain)
+    }
+
+    /// 是否套用 SRT 規範的檔尾空行與時間軸檢查
+    pub fn is_srt(self) -> bool {
+        self == Format::Srt
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_reads_headers() {
+        assert_eq!(
+            Format::sniff("\u{feff}WEBVTT\n\n00:01.000 --> 00:02.000\n字"),
+            Some(Format::WebVtt)
+        );
+        assert_eq!(
+            Format::sniff("; 註解\n[Script Info]\nTitle: x"),
+            Some(Format::Ass)
+        );
+        assert_eq!(Format::sniff("<SAMI>\n<BODY>"), Some(Format::Sami));
+        assert_eq!(
+            Format::sniff("<?xml version=\"1.0\"?>\n<tt xmlns=\"http://www.w3.org/ns/ttml\">"),
+            Some(Format::Ttml)
+        );
+    }
+
+    #[test]
+    fn sniff_counts_timelines() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\n字\n";
+        assert_eq!(Format::sniff(srt), Some(Format::Srt));
+        // 沒有檔頭的 WebVTT 式時間軸視為 SRT
+        assert_eq!(
+            Format::sniff("00:00:01.000 --> 00:00:02.000\n字"),
+            Some(Format::Srt)
+        );
+        assert_eq!(
+            Format::sniff("0:00:01.000,0:00:02.000\n字"),
+            Some(Format::Sbv)
+        );
+        assert_eq!(
+            Format::sniff("{1}{1}25\n{25}{50}字"),
+            Some(Format::MicroDvd)
+        );
+        assert_eq!(Format::sniff("[ti:歌]\n[00:01.00]字"), Some(Format::Lrc));
+        // 多數決：一行像 LRC 的筆記不會壓過 SRT 時間軸
+        let mixed = "[ar:某人]\n1\n00:00:01,000 --> 00:00:02,000\n字\n\n2\n00:00:03,000 --> 00:00:04,000\n字\n";
+        assert_eq!(Format::sniff(mixed), Some(Format::Srt));
+        assert_eq!(Format::sniff("筆記\n1. 第一點\n"), None);
+    }
+
+    #[test]
+    fn detect_and_resolve_fall_back() {
+        assert_eq!(Format::detect("筆記"), Format::Plain);
+        // 內容優先於副檔名
+        assert_eq!(
+            Format::resolve("e1.ass", "1\n00:00:01,000 --> 00:00:02,000\n字\n"),
+            Format::Srt
+        );
+        assert_eq!(Format::resolve("notes.md", "# 標題"), Format::Markdown);
+        assert_eq!(Format::resolve("test1.srt.txt", "字"), Format::Plain);
+        assert_eq!(Format::resolve("e1.SBV", "字"), Format::Sbv);
+    }
+
+    #[test]
+    fn names_and_extensions() {
+        for &name in &FORMAT_NAMES[1..] {
+            let f = Format::from_name(name).unwrap();
+            assert_eq!(f.name(), name);
+            assert_eq!(
+                Format::from_path(format!("a.{}", f.extension())).unwrap_or(Format::Plain),
+                f
+            );
+        }
+        assert_eq!(Format::from_name("auto"), None);
+        assert_eq!(Format::from_name(" DFXP "), Some(Format::Ttml));
+        assert_eq!(Format::from_path("a"), None);
+    }
+}
@@ -2,18 +2,32 @@
 // CW Subtitle Brain Module (v1.9.3)
 // ==========================================
 
+pub mod convert;
+pub mod encoding;
+pub mod error;
+pub mod format;
+pub mod markdown;
+pub mod mojibake;
+pub mod subtitle;
+pub mod ttml;
+
+pub use convert::{convert_str, ConversionResult, ConvertOptions};
+pub use error::{Error, Result};
+pub use format::Format;
+
 pub mod report_format {
+    use serde::Serialize;
     use std::path::PathBuf;
     use std::time::Duration;
 
-    #[derive(PartialEq, Debug, Clone)]
+    #[derive(PartialEq, Debug, Clone, Serialize)]
     pub enum ResultStatus {
         Success,
         VerifWarning,
         ConvertError,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct SubtitleIssue {
         pub line: usize,
         pub message: String,
@@ -26,27 +40,245 @@ pub mod report_format {
         pub temp_log_path: PathBuf,
         pub status: ResultStatus,
         pub issues: Vec<SubtitleIssue>,
+        /// 保留下來供預覽的對照行 (串流模式下只收集開頭數行)
         pub translated_pairs: Vec<(usize, String, String)>,
+        /// 轉換前後不同的行數
+        pub changed_lines: usize,
         pub duration: Duration,
     }
+
+    /// 稽核日誌 (JSON Lines) 的一筆紀錄，對應一個輸入檔。
+    #[derive(Debug, Clone, Serialize)]
+    pub struct AuditRecord {
+        pub timestamp: String,
+        pub input: String,
+        pub output: String,
+        pub input_sha256: String,
+        pub output_sha256: Option<String>,
+        pub encoding: String,
+        pub conversion: String,
+        pub profile: Option<String>,
+        /// 使用的 OpenCC 詞典設定與 cw 版本，例如 `opencc:s2twp.json@cw-1.9.3`
+        pub glossary: String,
+        pub status: ResultStatus,
+        pub issues: Vec<SubtitleIssue>,
+        pub fixes: Vec<String>,
+        pub duration_ms: u128,
+    }
 }
 
 pub mod core {
-    use crate::report_format::{ResultStatus, SubtitleIssue};
-    use chardetng::EncodingDetector;
+    use crate::encoding;
+    use crate::error::{Error, Result};
+    use crate::format::Format;
+    use crate::markdown;
+    use crate::mojibake;
+    use crate::report_format::{AuditRecord, ResultStatus, SubtitleIssue};
+    use crate::subtitle;
+    use crate::ttml;
+    use chrono::format::{Item, StrftimeItems};
     use chrono::Local;
+    use globset::{Glob, GlobSet, GlobSetBuilder};
     use opencc_rust::{DefaultConfig, OpenCC};
     use regex::Regex;
-    use std::collections::HashMap;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
     use std::env;
     use std::fs::{self, File, OpenOptions};
-    use std::io::{self, Write};
+    use std::io::{self, Read, Write};
     use std::path::{Path, PathBuf};
     use std::process::Command;
+    use std::sync::LazyLock;
 
     // --- [ 功能塊: 配置 ] ---
+    pub const LOG_LEVELS: &[&str] = &["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+    pub const SMTP_SECURITY: &[&str] = &["starttls", "tls", "none"];
+    pub const LINE_ENDINGS: &[&str] = &["auto", "lf", "crlf"];
+    pub const BOM_MODES: &[&str] = &["auto", "on", "off"];
+    /// `auto` 依 phrase_mode 在 s2t / s2twp 之間切換 (舊行為)。
+    pub const CONVERSIONS: &[&str] = &[
+        "auto", "s2t", "s2tw", "s2twp", "s2hk", "t2s", "tw2s", "tw2sp", "hk2s", "t2tw", "t2hk",
+    ];
+
+    /// 設定值型別，用於嚴格驗證。
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ValueKind {
+        Bool,
+        Int,
+        Port,
+        /// 以 MB 計的大小，接受 `10`、`10MB`
+        Size,
+        Choice(&'static [&'static str]),
+        Webhook,
+        /// 檔名模板：非空且不含路徑分隔符
+        FileName,
+        /// `auto` 或 encoding_rs 認得的編碼標籤
+        Encoding,
+        /// `auto` 或正的影格率，例如 `23.976`
+        FrameRate,
+        Text,
+    }
+
+    /// 所有已知設定鍵、內建預設值與型別，亦決定 `cw config show` 的列印順序。
+    pub const CONFIG_KEYS: &[(&str, &str, ValueKind)] = &[
+        ("profile", "", ValueKind::Text),
+        ("conversion", "auto", ValueKind::Choice(CONVERSIONS)),
+        ("phrase_mode", "false", ValueKind::Bool),
+        ("verbosity", "1", ValueKind::Int),
+        ("discord_webhook", "", ValueKind::Webhook),
+        ("discord_thread_id", "", ValueKind::Text),
+        ("discord_thread_name", "", ValueKind::Text),
+        ("discord_template", "", ValueKind::Text),
+        ("auto_discord", "false", ValueKind::Bool),
+        ("mention_id", "", ValueKind::Text),
+        ("discord_show_errors", "false", ValueKind::Bool),
+        ("show_stats", "false", ValueKind::Bool),
+        ("discord_interval", "2", ValueKind::Int),
+        ("translate_error", "true", ValueKind::Bool),
+        ("log_directory", "./logs", ValueKind::Text),
+        ("log_file_prefix", "cw", ValueKind::Text),
+        ("log_file_date_format", "%Y-%m-%d", ValueKind::Text),
+        ("log_level", "INFO", ValueKind::Choice(LOG_LEVELS)),
+        ("log_max_size", "10MB", ValueKind::Size),
+        ("log_backup_count", "5", ValueKind::Int),
+        ("full_preview", "false", ValueKind::Bool),
+        ("jobs", "1", ValueKind::Int),
+        ("line_ending", "auto", ValueKind::Choice(LINE_ENDINGS)),
+        ("bom", "auto", ValueKind::Choice(BOM_MODES)),
+        ("input_encoding", "auto", ValueKind::Encoding),
+        ("repair_mojibake", "false", ValueKind::Bool),
+        ("lrc_metadata", "false", ValueKind::Bool),
+        (
+            "output_encoding",
+            "utf-8",
+            ValueKind::Choice(encoding::OUTPUT_ENCODINGS),
+        ),
+        (
+            "input_extensions",
+            "srt,ass,ssa,vtt,sub,sbv,smi,ttml,dfxp,lrc",
+            ValueKind::Text,
+        ),
+        (
+            "format",
+            "auto",
+            ValueKind::Choice(crate::format::FORMAT_NAMES),
+        ),
+        (
+            "output_format",
+            "same",
+            ValueKind::Choice(subtitle::OUTPUT_FORMATS),
+        ),
+        ("frame_rate", "auto", ValueKind::FrameRate),
+        ("output_dir", "", ValueKind::Text),
+        (
+            "output_template",
+            DEFAULT_OUTPUT_TEMPLATE,
+            ValueKind::FileName,
+        ),
+        ("audit_log", "", ValueKind::Text),
+        ("auto_mail", "false", ValueKind::Bool),
+        ("smtp_host", "", ValueKind::Text),
+        ("smtp_port", "587", ValueKind::Port),
+        (
+            "smtp_security",
+            "starttls",
+            ValueKind::Choice(SMTP_SECURITY),
+        ),
+        ("smtp_user", "", ValueKind::Text),
+        ("smtp_password", "", ValueKind::Text),
+        ("smtp_from", "", ValueKind::Text),
+        ("smtp_to", "", ValueKind::Text),
+        ("mail_max_size", "20MB", ValueKind::Size),
+        ("mail_zip", "false", ValueKind::Bool),
+    ];
+
+    /// 設定值的來源層，後者覆蓋前者。
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ConfigSource {
+        Default,
+        System(PathBuf),
+        Install(PathBuf),
+        User(PathBuf),
+        Project(PathBuf),
+        /// `[profile.名稱]` 區段，附帶定義它的檔案
+        Profile(String, Box<ConfigSource>),
+        Env(String),
+        Cli,
+    }
+
+    impl std::fmt::Display for ConfigSource {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                ConfigSource::Default => write!(f, "預設"),
+                ConfigSource::System(p) => write!(f, "系統 {}", p.display()),
+                ConfigSource::Install(p) => write!(f, "安裝目錄 {}", p.display()),
+                ConfigSource::User(p) => write!(f, "使用者 {}", p.display()),
+                ConfigSource::Project(p) => write!(f, "專案 {}", p.display()),
+                ConfigSource::Profile(n, inner) => write!(f, "profile.{} @ {}", n, inner),
+                ConfigSource::Env(k) => write!(f, "環境變數 {}", k),
+                ConfigSource::Cli => write!(f, "命令列"),
+            }
+        }
+    }
+
+    /// 設定檔的位置與環境變數。`Config::load_layered` 取自實際環境，
+    /// 測試可改用 `Config::load_from` 指定暫存目錄，不受本機設定影響。
+    #[derive(Debug, Clone, Default)]
+    pub struct ConfigRoots {
+        /// 系統設定檔 (Unix 為 `/etc/cw/cw.cfg`)
+        pub system: Option<PathBuf>,
+        /// 執行檔旁的 `cw.cfg`
+        pub install: Option<PathBuf>,
+        /// 使用者設定檔 (`$XDG_CONFIG_HOME/cw/cw.cfg` 等)
+        pub user: Option<PathBuf>,
+        /// 專案設定自此目錄往上尋找；`None` 不尋找
+        pub project_start: Option<PathBuf>,
+        /// 往上尋找時最後檢查的目錄；`None` 找到根目錄
+        pub project_ceiling: Option<PathBuf>,
+        /// `CW_*` 環境變數
+        pub env: Vec<(String, String)>,
+    }
+
+    impl ConfigRoots {
+        /// 實際環境；`anchor` 為輸入檔或目錄，未指定時自工作目錄往上找專案設定。
+        /// `CW_CONFIG_CEILING` 可限制往上尋找的範圍 (類似 `GIT_CEILING_DIRECTORIES`)。
+        pub fn from_env(anchor: Option<&Path>) -> Self {
+            let mut exe_dir = env::current_exe().unwrap_or_default();
+            exe_dir.pop();
+            Self {
+                system: cfg!(unix).then(|| PathBuf::from("/etc/cw/cw.cfg")),
+                install: Some(exe_dir.join("cw.cfg")),
+                user: user_config_dir().map(|d| d.join("cw").join("cw.cfg")),
+                project_start: project_start(anchor),
+                project_ceiling: env::var_os("CW_CONFIG_CEILING")
+                    .filter(|v| !v.is_empty())
+                    .map(PathBuf::from),
+                env: env::vars().filter(|(k, _)| k.starts_with("CW_")).collect(),
+            }
+        }
+
+        fn var(&self, name: &str) -> Option<&str> {
+            self.env
+                .iter()
+                .rev()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ConfigEntry {
+        pub value: String,
+        pub source: ConfigSource,
+    }
+
     pub struct Config {
+        pub profile: String,
+        pub conversion: String,
         pub discord_webhook: String,
+        pub discord_thread_id: String,
+        pub discord_thread_name: String,
+        pub discord_template: String,
         pub phrase_mode: bool,
         pub verbosity: u32,
         pub auto_discord: bool,
@@ -62,93 +294,1139 @@ pub mod core {
         pub show_stats: bool,
         pub discord_show_errors: bool,
         pub full_preview: bool,
+        /// 同時處理的檔案數，0 表示依 CPU 核心數
+        pub jobs: usize,
+        pub line_ending: LineEnding,
+        pub bom: Bom,
+        /// 指定的輸入編碼；`None` 為自動偵測
+        pub input_encoding: Option<&'static encoding_rs::Encoding>,
+        pub output_encoding: encoding::OutputEncoding,
+        pub repair_mojibake: bool,
+        /// 掃描目錄時收錄的副檔名 (小寫、不含點)
+        pub input_extensions: Vec<String>,
+        /// 指定的字幕格式；`None` 為依內容判斷
+        pub format: Option<Format>,
+        /// 輸出格式；`None` 沿用輸入格式
+        pub output_format: Option<Format>,
+        /// MicroDVD 影格率；`None` 取檔頭或預設值
+        pub frame_rate: Option<f64>,
+        /// LRC 一併轉換 `[ti:]`、`[ar:]` 等標籤的值
+        pub lrc_metadata: bool,
+        pub output_dir: String,
+        pub output_template: String,
+        pub audit_log: String,
+        pub auto_mail: bool,
+        pub smtp_host: String,
+        pub smtp_port: u16,
+        pub smtp_security: String,
+        pub smtp_user: String,
+        pub smtp_password: String,
+        pub smtp_from: String,
+        pub smtp_to: String,
+        pub mail_max_size_mb: u64,
+        pub mail_zip: bool,
+        /// 合併後的原始值與來源，供 `cw config show` 使用。
+        pub entries: BTreeMap<String, ConfigEntry>,
+        /// 載入過程中發現的錯誤與警告；無效值不會套用，保留較低層的設定。
+        pub diagnostics: Vec<ConfigDiagnostic>,
+        /// 所有設定檔中定義過的 profile 名稱
+        pub profiles: Vec<String>,
     }
 
     impl Config {
         pub fn load() -> Self {
-            let mut exe_path = env::current_exe().unwrap_or_default();
-            exe_path.pop();
-            let cfg_path = exe_path.join("cw.cfg");
-            let mut map = HashMap::new();
-            if let Ok(content) = fs::read_to_string(&cfg_path) {
-                for line in content.lines() {
-                    let clean = line.split('#').next().unwrap_or("").trim();
-                    if let Some((k, v)) = clean.split_once('=') {
-                        map.insert(k.trim().to_string(), v.trim().trim_matches('"').to_string());
+            Self::load_layered(None, &[])
+        }
+
+        /// 依序疊加：內建預設 → /etc → 執行檔旁 → 使用者目錄 → 專案 (自 `anchor` 往上找)
+        /// → 選用的 profile (含繼承鏈) → `CW_*` 環境變數 → 命令列覆寫。
+        ///
+        /// profile 由命令列 `--profile`、`CW_PROFILE` 或設定檔中的 `profile` 鍵決定；
+        /// 各檔案的同名 `[profile.名稱]` 區段會依載入順序合併。
+        pub fn load_layered(anchor: Option<&Path>, cli: &[(String, String)]) -> Self {
+            Self::load_from(&ConfigRoots::from_env(anchor), cli)
+        }
+
+        /// 同 `load_layered`，但設定檔位置與環境變數取自 `roots`，不讀實際環境。
+        pub fn load_from(roots: &ConfigRoots, cli: &[(String, String)]) -> Self {
+            let mut entries = BTreeMap::new();
+            let mut diagnostics = Vec::new();
+            let mut profiles: BTreeMap<String, Vec<(ConfigSource, CfgPair)>> = BTreeMap::new();
+            for (k, v, _) in CONFIG_KEYS {
+                entries.insert(
+                    k.to_string(),
+                    ConfigEntry {
+                        value: v.to_string(),
+                        source: ConfigSource::Default,
+                    },
+                );
+            }
+            for (path, source) in Self::discover(roots) {
+                let content = match fs::read_to_string(&path) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        diagnostics.push(ConfigDiagnostic {
+                            source,
+                            line: 0,
+                            kind: ConfigErrorKind::Syntax(format!("無法讀取: {}", e)),
+                        });
+                        continue;
+                    }
+                };
+                let mark = diagnostics.len();
+                let (pairs, errs) = parse_cfg(&content);
+                for (line, kind) in errs {
+                    diagnostics.push(ConfigDiagnostic {
+                        source: source.clone(),
+                        line,
+                        kind,
+                    });
+                }
+                for pair in pairs {
+                    match pair.profile.clone() {
+                        Some(name) => profiles
+                            .entry(name)
+                            .or_default()
+                            .push((source.clone(), pair)),
+                        None => apply_entry(
+                            &mut entries,
+                            &mut diagnostics,
+                            &source,
+                            pair.line,
+                            &pair.key,
+                            pair.value,
+                        ),
                     }
                 }
+                diagnostics[mark..].sort_by_key(|d| d.line);
+            }
+            let selected = cli
+                .iter()
+                .rev()
+                .find(|(k, _)| k == "profile")
+                .map(|(_, v)| (v.clone(), ConfigSource::Cli))
+                .or_else(|| {
+                    roots
+                        .var("CW_PROFILE")
+                        .map(|v| (v.to_string(), ConfigSource::Env("CW_PROFILE".to_string())))
+                })
+                .or_else(|| {
+                    entries
+                        .get("profile")
+                        .map(|e| (e.value.clone(), e.source.clone()))
+                })
+                .filter(|(v, _)| !v.is_empty());
+            if let Some((name, origin)) = selected {
+                for (src, pair) in resolve_profile(&name, &origin, &profiles, &mut diagnostics) {
+                    let src = ConfigSource::Profile(
+                        pair.profile.clone().unwrap_or_default(),
+                        Box::new(src),
+                    );
+                    apply_entry(
+                        &mut entries,
+                        &mut diagnostics,
+                        &src,
+                        pair.line,
+                        &pair.key,
+                        pair.value,
+                    );
+                }
+            }
+            for (k, _, _) in CONFIG_KEYS {
+                let var = format!("CW_{}", k.to_uppercase());
+                if let Some(v) = roots.var(&var) {
+                    apply_entry(
+                        &mut entries,
+                        &mut diagnostics,
+                        &ConfigSource::Env(var),
+                        0,
+                        k,
+                        v.to_string(),
+                    );
+                }
+            }
+            for (k, v) in cli {
+                apply_entry(
+                    &mut entries,
+                    &mut diagnostics,
+                    &ConfigSource::Cli,
+                    0,
+                    k,
+                    v.clone(),
+                );
+            }
+            let mut cfg = Self::from_entries(entries);
+            cfg.diagnostics = diagnostics;
+            cfg.profiles = profiles.into_keys().collect();
+            cfg
+        }
+
+        /// 目前的日誌檔路徑：`log_directory/前綴_日期.log`，日期格式為空時不加日期。
+        pub fn log_path(&self) -> PathBuf {
+            // 無效的 strftime 格式在格式化時會 panic，先檢查再使用
+            let fmt = &self.log_file_date_format;
+            let valid = StrftimeItems::new(fmt).all(|i| !matches!(i, Item::Error));
+            let date = if fmt.is_empty() || !valid {
+                String::new()
+            } else {
+                format!("_{}", Local::now().format(fmt))
+            };
+            Path::new(&self.log_directory).join(format!("{}{}.log", self.log_file_prefix, date))
+        }
+
+        /// 實際使用的 OpenCC 轉換設定。
+        pub fn opencc_config(&self) -> DefaultConfig {
+            match self.conversion.as_str() {
+                "s2t" => DefaultConfig::S2T,
+                "s2tw" => DefaultConfig::S2TW,
+                "s2twp" => DefaultConfig::S2TWP,
+                "s2hk" => DefaultConfig::S2HK,
+                "t2s" => DefaultConfig::T2S,
+                "tw2s" => DefaultConfig::TW2S,
+                "tw2sp" => DefaultConfig::TW2SP,
+                "hk2s" => DefaultConfig::HK2S,
+                "t2tw" => DefaultConfig::T2TW,
+                "t2hk" => DefaultConfig::T2HK,
+                _ if self.phrase_mode => DefaultConfig::S2TWP,
+                _ => DefaultConfig::S2T,
+            }
+        }
+
+        /// 依設定組出串流轉換選項。
+        pub fn stream_options(
+            &self,
+            fix_trailing_newline: bool,
+            collect: Collect,
+        ) -> StreamOptions {
+            StreamOptions {
+                fix_trailing_newline,
+                diagnose: false,
+                collect,
+                line_ending: self.line_ending,
+                bom: self.bom,
+                input_encoding: self.input_encoding,
+                output_encoding: self.output_encoding,
+                repair_mojibake: self.repair_mojibake,
+                format: self.format,
+                output_format: self.output_format,
+                frame_rate: self.frame_rate,
+                lrc_metadata: self.lrc_metadata,
+            }
+        }
+
+        /// 此檔採用的格式：設定指定優先，否則依內容與副檔名判斷。
+        pub fn format_for(&self, path: &str, text: &str) -> Format {
+            self.format.unwrap_or_else(|| Format::resolve(path, text))
+        }
+
+        /// 顯示用的轉換名稱，例如 `S2TWP`。
+        pub fn conversion_label(&self) -> String {
+            match self.conversion.as_str() {
+                "auto" | "" if self.phrase_mode => "S2TWP".to_string(),
+                "auto" | "" => "S2T".to_string(),
+                c => c.to_uppercase(),
+            }
+        }
+
+        /// 是否有任何錯誤等級的設定問題 (警告不算)。
+        pub fn has_errors(&self) -> bool {
+            self.diagnostics.iter().any(ConfigDiagnostic::is_error)
+        }
+
+        /// 列出存在的設定檔 (由低到高優先)。
+        pub fn discover(roots: &ConfigRoots) -> Vec<(PathBuf, ConfigSource)> {
+            let mut found = Vec::new();
+            let mut push = |p: PathBuf, src: fn(PathBuf) -> ConfigSource| {
+                if p.is_file() && !found.iter().any(|(q, _)| q == &p) {
+                    found.push((p.clone(), src(p)));
+                }
+            };
+            let files = [
+                (
+                    &roots.system,
+                    ConfigSource::System as fn(PathBuf) -> ConfigSource,
+                ),
+                (&roots.install, ConfigSource::Install),
+                (&roots.user, ConfigSource::User),
+            ];
+            for (path, src) in files {
+                if let Some(p) = path {
+                    push(p.clone(), src);
+                }
             }
+            if let Some(p) = roots
+                .project_start
+                .as_deref()
+                .and_then(|start| find_project_cfg(start, roots.project_ceiling.as_deref()))
+            {
+                push(p, ConfigSource::Project);
+            }
+            found
+        }
+
+        fn from_entries(entries: BTreeMap<String, ConfigEntry>) -> Self {
+            let get = |k: &str| entries.get(k).map(|e| e.value.clone()).unwrap_or_default();
+            let flag = |k: &str| get(k) == "true";
             Self {
-                discord_webhook: map.get("discord_webhook").cloned().unwrap_or_default(),
-                phrase_mode: map.get("phrase_mode").map(|v| v == "true").unwrap_or(false),
-                verbosity: map
-                    .get("verbosity")
-                    .and_then(|v| v.parse().ok())
-                    .unwrap_or(1),
-                auto_discord: map
-                    .get("auto_discord")
-                    .map(|v| v == "true")
-                    .unwrap_or(false),
-                log_directory: map
-                    .get("log_directory")
-                    .cloned()
-                    .unwrap_or_else(|| "./logs".to_string()),
-                log_file_prefix: map
-                    .get("log_file_prefix")
-                    .cloned()
-                    .unwrap_or_else(|| "cw".to_string()),
-                log_file_date_format: map
-                    .get("log_file_date_format")
-                    .cloned()
-                    .unwrap_or_else(|| "%Y-%m-%d".to_string()),
-                log_level: map
-                    .get("log_level")
-                    .cloned()
-                    .unwrap_or_else(|| "INFO".to_string()),
-                log_max_size_mb: map
-                    .get("log_max_size")
-                    .and_then(|v| v.replace("MB", "").trim().parse().ok())
-                    .unwrap_or(10),
-                log_backup_count: map
-                    .get("log_backup_count")
-                    .and_then(|v| v.parse().ok())
-                    .unwrap_or(5),
-                mention_id: map.get("mention_id").cloned().unwrap_or_default(),
-                discord_interval: map
-                    .get("discord_interval")
-                    .and_then(|v| v.parse().ok())
-                    .unwrap_or(2),
-                translate_error: map
-                    .get("translate_error")
-                    .map(|v| v == "true")
-                    .unwrap_or(true),
-                show_stats: map.get("show_stats").map(|v| v == "true").unwrap_or(false),
-                discord_show_errors: map
-                    .get("discord_show_errors")
-                    .map(|v| v == "true")
-                    .unwrap_or(false),
-                full_preview: map
-                    .get("full_preview")
-                    .map(|v| v == "true")
-                    .unwrap_or(false),
-            }
-        }
-
-        pub fn generate_default() -> io::Result<()> {
+                profile: get("profile"),
+                conversion: get("conversion"),
+                discord_webhook: get("discord_webhook"),
+                discord_thread_id: get("discord_thread_id"),
+                discord_thread_name: get("discord_thread_name"),
+                discord_template: get("discord_template"),
+                phrase_mode: flag("phrase_mode"),
+                verbosity: get("verbosity").parse().unwrap_or(1),
+                auto_discord: flag("auto_discord"),
+                log_directory: get("log_directory"),
+                log_file_prefix: get("log_file_prefix"),
+                log_file_date_format: get("log_file_date_format"),
+                log_level: get("log_level"),
+                log_max_size_mb: parse_size_mb(&get("log_max_size")).unwrap_or(10),
+                log_backup_count: get("log_backup_count").parse().unwrap_or(5),
+                mention_id: get("mention_id"),
+                discord_interval: get("discord_interval").parse().unwrap_or(2),
+                translate_error: flag("translate_error"),
+                show_stats: flag("show_stats"),
+                discord_show_errors: flag("discord_show_errors"),
+                full_preview: flag("full_preview"),
+                jobs: get("jobs").parse().unwrap_or(1),
+                line_ending: match get("line_ending").as_str() {
+                    "lf" => LineEnding::Lf,
+                    "crlf" => LineEnding::CrLf,
+                    _ => LineEnding::Preserve,
+                },
+                bom: match get("bom").as_str() {
+                    "on" => Bom::Always,
+                    "off" => Bom::Never,
+                    _ => Bom::Preserve,
+                },
+                input_encoding: encoding::lookup(&get("input_encoding")).unwrap_or(None),
+                output_encoding: encoding::OutputEncoding::from_name(&get("output_encoding"))
+                    .unwrap_or_default(),
+                repair_mojibake: flag("repair_mojibake"),
+                input_extensions: parse_extensions(&get("input_extensions")),
+                format: Format::from_name(&get("format")),
+                output_format: Format::from_name(&get("output_format")),
+                frame_rate: parse_frame_rate(&get("frame_rate")),
+                lrc_metadata: flag("lrc_metadata"),
+                output_dir: get("output_dir"),
+                output_template: get("output_template"),
+                audit_log: get("audit_log"),
+                auto_mail: flag("auto_mail"),
+                smtp_host: get("smtp_host"),
+                smtp_port: get("smtp_port").parse().unwrap_or(587),
+                smtp_security: get("smtp_security"),
+                smtp_user: get("smtp_user"),
+                smtp_password: get("smtp_password"),
+                smtp_from: get("smtp_from"),
+                smtp_to: get("smtp_to"),
+                mail_max_size_mb: parse_size_mb(&get("mail_max_size")).unwrap_or(20),
+                mail_zip: flag("mail_zip"),
+                entries,
+                diagnostics: Vec::new(),
+                profiles: Vec::new(),
+            }
+        }
+
+        /// 設定有錯誤等級的問題時回傳 `Error::Config`；警告不影響。
+        pub fn check(&self) -> Result<()> {
+            let errors: Vec<ConfigDiagnostic> = self
+                .diagnostics
+                .iter()
+                .filter(|d| d.is_error())
+                .cloned()
+                .collect();
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::Config(errors))
+            }
+        }
+
+        pub fn generate_default() -> Result<()> {
             let mut path = env::current_exe().unwrap_or_default();
             path.pop();
             let cfg_path = path.join("cw.cfg");
             let template = include_str!("../default_cw.cfg");
-            fs::write(cfg_path, template)?;
+            fs::write(&cfg_path, template).map_err(|e| Error::io_at(cfg_path, e))?;
             println!("\x1b[1;32m✨ 已生成預設 cw.cfg\x1b[0m");
             Ok(())
         }
     }
 
+    /// 設定問題的種類；`UnknownKey`、`DuplicateKey` 屬警告，其餘為錯誤。
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ConfigErrorKind {
+        Syntax(String),
+        UnterminatedQuote,
+        UnsupportedSection(String),
+        UnknownKey(String),
+        DuplicateKey(String),
+        InvalidValue {
+            key: String,
+            value: String,
+            expected: String,
+        },
+        InvalidWebhook(String),
+        UnknownProfile(String),
+        ProfileCycle(String),
+    }
+
+    impl std::fmt::Display for ConfigErrorKind {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                ConfigErrorKind::Syntax(m) => write!(f, "語法錯誤：{}", m),
+                ConfigErrorKind::UnterminatedQuote => write!(f, "語法錯誤：引號未閉合"),
+                ConfigErrorKind::UnsupportedSection(n) => write!(f, "不支援的區段 [{}]", n),
+                ConfigErrorKind::UnknownKey(k) => write!(f, "未知的設定鍵 `{}`，已忽略", k),
+                ConfigErrorKind::DuplicateKey(k) => {
+                    write!(f, "重複的設定鍵 `{}`，以最後一次為準", k)
+                }
+                ConfigErrorKind::InvalidValue {
+                    key,
+                    value,
+                    expected,
+                } => write!(f, "`{}` 的值 \"{}\" 無效，應為 {}", key, value, expected),
+                ConfigErrorKind::UnknownProfile(n) => write!(f, "找不到 profile `{}`", n),
+                ConfigErrorKind::ProfileCycle(c) => write!(f, "profile 繼承形成循環：{}", c),
+                ConfigErrorKind::InvalidWebhook(v) => {
+                    write!(
+                        f,
+                        "discord_webhook \"{}\" 不是有效的 Discord webhook URL",
+                        v
+                    )
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ConfigDiagnostic {
+        pub source: ConfigSource,
+        /// 1 起算；環境變數與命令列為 0
+        pub line: usize,
+        pub kind: ConfigErrorKind,
+    }
+
+    impl ConfigDiagnostic {
+        pub fn is_error(&self) -> bool {
+            !matches!(
+                self.kind,
+                ConfigErrorKind::UnknownKey(_) | ConfigErrorKind::DuplicateKey(_)
+            )
+        }
+    }
+
+    impl std::fmt::Display for ConfigDiagnostic {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            if self.line > 0 {
+                write!(f, "{}:{}: {}", self.source, self.line, self.kind)
+            } else {
+                write!(f, "{}: {}", self.source, self.kind)
+            }
+        }
+    }
+
+    impl ValueKind {
+        fn expected(&self) -> String {
+            match self {
+                ValueKind::Bool => "true 或 false".to_string(),
+                ValueKind::Int => "非負整數".to_string(),
+                ValueKind::Port => "1-65535 的埠號".to_string(),
+                ValueKind::Size => "大小 (例如 10MB)".to_string(),
+                ValueKind::Choice(opts) => opts.join(" / "),
+                ValueKind::Webhook => "Discord webhook URL".to_string(),
+                ValueKind::FileName => "不含 / 或 \\ 的檔名模板".to_string(),
+                ValueKind::Encoding => "auto 或編碼名稱 (例如 gbk、big5、utf-16le)".to_string(),
+                ValueKind::FrameRate => "auto 或影格率 (例如 23.976、25)".to_string(),
+                ValueKind::Text => "文字".to_string(),
+            }
+        }
+    }
+
+    pub fn parse_size_mb(v: &str) -> Option<u64> {
+        let t = v.trim();
+        let t = t
+            .strip_suffix("MB")
+            .or_else(|| t.strip_suffix("mb"))
+            .unwrap_or(t);
+        t.trim().parse().ok()
+    }
+
+    /// `auto` 回傳 `None`；其他值需為 0 到 1000 之間的影格率。
+    pub fn parse_frame_rate(v: &str) -> Option<f64> {
+        v.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|f| *f > 0.0 && *f < 1000.0)
+    }
+
+    /// 依 `CONFIG_KEYS` 的型別驗證單一設定值。
+    pub fn validate_entry(key: &str, value: &str) -> Result<(), ConfigErrorKind> {
+        let Some((_, _, kind)) = CONFIG_KEYS.iter().find(|(k, _, _)| *k == key) else {
+            return Err(ConfigErrorKind::UnknownKey(key.to_string()));
+        };
+        let ok = match kind {
+            ValueKind::Bool => value == "true" || value == "false",
+            ValueKind::Int => value.parse::<u32>().is_ok(),
+            ValueKind::Port => value.parse::<u16>().map(|p| p > 0).unwrap_or(false),
+            ValueKind::Size => parse_size_mb(value).is_some(),
+            ValueKind::Choice(opts) => opts.contains(&value),
+            ValueKind::Webhook => {
+                if value.is_empty() || is_discord_webhook(value) {
+                    return Ok(());
+                }
+                return Err(ConfigErrorKind::InvalidWebhook(value.to_string()));
+            }
+            ValueKind::FileName => !value.trim().is_empty() && !value.contains(['/', '\\']),
+            ValueKind::Encoding => encoding::lookup(value).is_ok(),
+            ValueKind::FrameRate => value == "auto" || parse_frame_rate(value).is_some(),
+            ValueKind::Text => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(ConfigErrorKind::InvalidValue {
+                key: key.to_string(),
+                value: value.to_string(),
+                expected: kind.expected(),
+            })
+        }
+    }
+
+    fn is_discord_webhook(url: &str) -> bool {
+        let Some(rest) = url.strip_prefix("https://") else {
+            return false;
+        };
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host_ok = ["discord.com", "discordapp.com"]
+            .iter()
+            .any(|h| host == *h || host.ends_with(&format!(".{}", h)));
+        let mut parts = path.split('/');
+        host_ok
+            && parts.next() == Some("api")
+            && parts.next() == Some("webhooks")
+            && parts.next().is_some_and(|id| !id.is_empty())
+            && parts.next().is_some_and(|tok| !tok.is_empty())
+    }
+
+    fn apply_entry(
+        entries: &mut BTreeMap<String, ConfigEntry>,
+        diagnostics: &mut Vec<ConfigDiagnostic>,
+        source: &ConfigSource,
+        line: usize,
+        key: &str,
+        value: String,
+    ) {
+        match validate_entry(key, &value) {
+            Ok(()) => {
+                entries.insert(
+                    key.to_string(),
+                    ConfigEntry {
+                        value,
+                        source: source.clone(),
+                    },
+                );
+            }
+            Err(kind) => diagnostics.push(ConfigDiagnostic {
+                source: source.clone(),
+                line,
+                kind,
+            }),
+        }
+    }
+
+    /// 設定檔中的一組鍵值；`profile` 為所屬的 `[profile.名稱]` 區段。
+    #[derive(Debug, Clone)]
+    pub struct CfgPair {
+        pub line: usize,
+        pub profile: Option<String>,
+        pub key: String,
+        pub value: String,
+    }
+
+    /// 鍵值與 (行號, 問題)
+    pub type ParsedCfg = (Vec<CfgPair>, Vec<(usize, ConfigErrorKind)>);
+
+    /// 沿 `inherits` 鏈展開 profile，回傳由最上層祖先到 `name` 本身的鍵值。
+    fn resolve_profile(
+        name: &str,
+        origin: &ConfigSource,
+        profiles: &BTreeMap<String, Vec<(ConfigSource, CfgPair)>>,
+        diagnostics: &mut Vec<ConfigDiagnostic>,
+    ) -> Vec<(ConfigSource, CfgPair)> {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut cur = name;
+        loop {
+            if chain.contains(&cur) {
+                chain.push(cur);
+                diagnostics.push(ConfigDiagnostic {
+                    source: origin.clone(),
+                    line: 0,
+                    kind: ConfigErrorKind::ProfileCycle(chain.join(" → ")),
+                });
+                return Vec::new();
+            }
+            let Some(items) = profiles.get(cur) else {
+                diagnostics.push(ConfigDiagnostic {
+                    source: origin.clone(),
+                    line: 0,
+                    kind: ConfigErrorKind::UnknownProfile(cur.to_string()),
+                });
+                break;
+            };
+            chain.push(cur);
+            match items.iter().rev().find(|(_, p)| p.key == "inherits") {
+                Some((_, parent)) => cur = parent.value.as_str(),
+                None => break,
+            }
+        }
+        chain
+            .iter()
+            .rev()
+            .filter_map(|n| profiles.get(*n))
+            .flatten()
+            .filter(|(_, p)| p.key != "inherits")
+            .cloned()
+            .collect()
+    }
+
+    /// 嚴格 INI 方言：`鍵 = 值`，`#`/`;` 起頭為註解；值可加雙引號，引號內的 `#` 保留，
+    /// 支援 `\"` 與 `\\` 跳脫。未加引號的值遇到「空白 + #」才視為行尾註解。
+    /// 唯一允許的區段是 `[profile.名稱]`，區段內可用 `inherits = "其他名稱"`。
+    pub fn parse_cfg(content: &str) -> ParsedCfg {
+        let mut pairs: Vec<CfgPair> = Vec::new();
+        let mut errs = Vec::new();
+        let mut section: Option<String> = None;
+        let mut skipping = false;
+        for (idx, raw) in content.lines().enumerate() {
+            let line = idx + 1;
+            let t = raw.trim().trim_start_matches('\u{feff}');
+            if t.is_empty() || t.starts_with('#') || t.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = t.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+                let name = name.trim();
+                match name.strip_prefix("profile.").map(str::trim) {
+                    Some(p) if !p.is_empty() => {
+                        section = Some(p.to_string());
+                        skipping = false;
+                    }
+                    _ => {
+                        errs.push((line, ConfigErrorKind::UnsupportedSection(name.to_string())));
+                        // 不認得的區段整段略過，避免其鍵值誤套到全域
+                        skipping = true;
+                    }
+                }
+                continue;
+            }
+            if skipping {
+                continue;
+            }
+            let Some((k, v)) = t.split_once('=') else {
+                errs.push((line, ConfigErrorKind::Syntax("缺少 `=`".to_string())));
+                continue;
+            };
+            let key = k.trim();
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                errs.push((
+                    line,
+                    ConfigErrorKind::Syntax(format!("無效的鍵名 `{}`", key)),
+                ));
+                continue;
+            }
+            match parse_cfg_value(v) {
+                Ok(val) => {
+                    if pairs.iter().any(|p| p.key == key && p.profile == section) {
+                        errs.push((line, ConfigErrorKind::DuplicateKey(key.to_string())));
+                    }
+                    if key == "inherits" && section.is_none() {
+                        errs.push((
+                            line,
+                            ConfigErrorKind::Syntax("inherits 只能用於 profile 區段".to_string()),
+                        ));
+                        continue;
+                    }
+                    pairs.push(CfgPair {
+                        line,
+                        profile: section.clone(),
+                        key: key.to_string(),
+                        value: val,
+                    });
+                }
+                Err(kind) => errs.push((line, kind)),
+            }
+        }
+        (pairs, errs)
+    }
+
+    fn parse_cfg_value(raw: &str) -> Result<String, ConfigErrorKind> {
+        let v = raw.trim();
+        let Some(rest) = v.strip_prefix('"') else {
+            let end = v
+                .char_indices()
+                .find(|&(i, c)| c == '#' && (i == 0 || v[..i].ends_with(char::is_whitespace)))
+                .map(|(i, _)| i)
+                .unwrap_or(v.len());
+            return Ok(v[..end].trim().to_string());
+        };
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, e @ ('"' | '\\'))) => out.push(e),
+                    Some((_, e)) => {
+                        out.push('\\');
+                        out.push(e);
+                    }
+                    None => return Err(ConfigErrorKind::UnterminatedQuote),
+                },
+                '"' => {
+                    let tail = rest[i + 1..].trim();
+                    if tail.is_empty() || tail.starts_with('#') || tail.starts_with(';') {
+                        return Ok(out);
+                    }
+                    return Err(ConfigErrorKind::Syntax(format!(
+                        "引號後有多餘內容 `{}`",
+                        tail
+                    )));
+                }
+                _ => out.push(c),
+            }
+        }
+        Err(ConfigErrorKind::UnterminatedQuote)
+    }
+
+    fn user_config_dir() -> Option<PathBuf> {
+        if let Some(x) = env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            return Some(PathBuf::from(x));
+        }
+        if cfg!(windows) {
+            return env::var_os("APPDATA").map(PathBuf::from);
+        }
+        env::var_os("HOME").map(|h| PathBuf::from(h).join(".config"))
+    }
+
+    /// 專案設定的起點：輸入檔所在目錄 (未指定時為工作目錄)，已轉為絕對路徑。
+    fn project_start(anchor: Option<&Path>) -> Option<PathBuf> {
+        let start = match anchor {
+            Some(p) if p.is_dir() => p.to_path_buf(),
+            Some(p) => p.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => env::current_dir().ok()?,
+        };
+        if start.as_os_str().is_empty() {
+            env::current_dir().ok()
+        } else {
+            Some(fs::canonicalize(&start).unwrap_or(start))
+        }
+    }
+
+    /// 自 `start` 逐層往上尋找最近的 cw.cfg，`ceiling` 為最後檢查的目錄。
+    fn find_project_cfg(start: &Path, ceiling: Option<&Path>) -> Option<PathBuf> {
+        let ceiling = ceiling.map(|c| fs::canonicalize(c).unwrap_or_else(|_| c.to_path_buf()));
+        for dir in start.ancestors() {
+            let p = dir.join("cw.cfg");
+            if p.is_file() {
+                return Some(p);
+            }
+            if ceiling.as_deref() == Some(dir) {
+                break;
+            }
+        }
+        None
+    }
+
+    // --- [ 功能塊: 輸入收集 ] ---
+    /// 將 `srt, .ASS;vtt` 之類的清單正規化為 `["srt", "ass", "vtt"]`。
+    pub fn parse_extensions(list: &str) -> Vec<String> {
+        list.split([',', ';', ' '])
+            .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect()
+    }
+
+    /// cw 自己產生的檔案：`*.tmp` 影子檔，以及 `x.srt.txt`、`x.srt.txt.txt` 等轉換成果。
+    pub fn is_cw_output(path: &Path) -> bool {
+        let name = match path.file_name() {
+            Some(n) => n.to_string_lossy().to_lowercase(),
+            None => return false,
+        };
+        if name.ends_with(".tmp") {
+            return true;
+        }
+        let mut stem = name.as_str();
+        let mut txt = false;
+        while let Some(s) = stem.strip_suffix(".txt") {
+            stem = s;
+            txt = true;
+        }
+        txt && [
+            ".srt", ".ass", ".ssa", ".vtt", ".sub", ".sbv", ".smi", ".ttml", ".dfxp", ".lrc",
+        ]
+        .iter()
+        .any(|e| stem.ends_with(e))
+    }
+
+    /// 目錄與萬用字元展開的篩選條件；命令列明確指定的檔案一律處理，不套用任何篩選。
+    pub struct InputFilter {
+        pub recursive: bool,
+        pub extensions: Vec<String>,
+        include: Option<GlobSet>,
+        exclude: Option<GlobSet>,
+        skip_dirs: Vec<PathBuf>,
+        outputs: Vec<OutputMatcher>,
+    }
+
+    impl InputFilter {
+        pub fn new(
+            recursive: bool,
+            extensions: Vec<String>,
+            include: &[String],
+            exclude: &[String],
+        ) -> Result<Self> {
+            Ok(Self {
+                recursive,
+                extensions,
+                include: build_globset(include)?,
+                exclude: build_globset(exclude)?,
+                skip_dirs: Vec::new(),
+                outputs: Vec::new(),
+            })
+        }
+
+        /// 略過目前輸出檔名模板的產物 (見 `OutputMatcher`)，避免重複執行時把上次的成果再轉一次。
+        /// 可多次呼叫，例如 `--to` 另有 `retarget_template`。
+        pub fn skip_outputs(mut self, template: &str, vars: &[(&str, &str)]) -> Self {
+            self.outputs.extend(OutputMatcher::new(template, vars));
+            self
+        }
+
+        fn is_output(&self, path: &Path) -> bool {
+            is_cw_output(path) || self.outputs.iter().any(|m| m.is_output(path))
+        }
+
+        /// 略過此目錄下的所有檔案，避免輸出目錄位於掃描範圍內時重複處理成果。
+        pub fn skip_dir(mut self, dir: &Path) -> Self {
+            if let Ok(d) = fs::canonicalize(dir) {
+                self.skip_dirs.push(d);
+            }
+            self
+        }
+
+        fn in_skipped_dir(&self, path: &Path) -> bool {
+            !self.skip_dirs.is_empty()
+                && fs::canonicalize(path)
+                    .is_ok_and(|p| self.skip_dirs.iter().any(|d| p.starts_with(d)))
+        }
+
+        /// `rel` 為相對於掃描根目錄的路徑；規則可比對完整相對路徑或檔名。
+        fn accepts(&self, rel: &Path, scanned: bool) -> bool {
+            let name = rel.file_name().map(Path::new).unwrap_or(rel);
+            let hit = |set: &GlobSet| set.is_match(rel) || set.is_match(name);
+            if self.exclude.as_ref().is_some_and(hit) {
+                return false;
+            }
+            if !scanned {
+                return true;
+            }
+            let ext = rel
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            (self.extensions.is_empty() || self.extensions.contains(&ext))
+                && self.include.as_ref().is_none_or(hit)
+        }
+    }
+
+    fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        let bad = |e: globset::Error| Error::parse(0, 0, e.to_string());
+        let mut b = GlobSetBuilder::new();
+        for p in patterns {
+            b.add(Glob::new(p).map_err(bad)?);
+        }
+        b.build().map(Some).map_err(bad)
+    }
+
+    /// 一個待處理檔案；`rel` 為相對於掃描根目錄的路徑，輸出到其他目錄時用來保留結構。
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct InputFile {
+        pub path: PathBuf,
+        pub rel: PathBuf,
+    }
+
+    /// 展開結果：待處理檔案 (已去重、排序)、被略過的檔案與原因、找不到的參數。
+    #[derive(Debug, Default)]
+    pub struct InputSet {
+        pub files: Vec<InputFile>,
+        pub skipped: Vec<(PathBuf, &'static str)>,
+        pub missing: Vec<String>,
+    }
+
+    /// 將命令列參數展開為檔案清單：檔案直接收錄、目錄依篩選條件掃描
+    /// (`recursive` 時含子目錄)、含 `* ? [` 的參數視為萬用字元比對。
+    /// 成果檔、輸出目錄與排除規則只用於展開出來的檔案，明確指定的檔案一律收錄。
+    pub fn collect_inputs(args: &[String], filter: &InputFilter) -> InputSet {
+        let mut set = InputSet::default();
+        let push = |set: &mut InputSet, path: PathBuf, rel: PathBuf, scanned: bool| {
+            if set.files.iter().any(|f| f.path == path) {
+                return;
+            }
+            if filter.is_output(&path) {
+                set.skipped.push((path, "cw 成果檔"));
+            } else if filter.in_skipped_dir(&path) {
+                set.skipped.push((path, "位於輸出目錄"));
+            } else if !filter.accepts(&rel, scanned) {
+                if !scanned {
+                    set.skipped.push((path, "符合排除規則"));
+                }
+            } else {
+                set.files.push(InputFile { path, rel });
+            }
+        };
+        for arg in args {
+            let path = PathBuf::from(arg);
+            if path.is_file() {
+                let rel = path.file_name().map(PathBuf::from).unwrap_or_default();
+                if !set.files.iter().any(|f| f.path == path) {
+                    set.files.push(InputFile { path, rel });
+                }
+            } else if path.is_dir() {
+                let depth = if filter.recursive { usize::MAX } else { 1 };
+                for entry in walk(&path, depth) {
+                    let rel = entry.strip_prefix(&path).unwrap_or(&entry).to_path_buf();
+                    push(&mut set, entry, rel, true);
+                }
+            } else if arg.contains(['*', '?', '[']) {
+                match expand_glob(arg, filter.recursive) {
+                    Some((base, files)) if !files.is_empty() => {
+                        for f in files {
+                            let rel = f.strip_prefix(&base).unwrap_or(&f).to_path_buf();
+                            push(&mut set, f, rel, false);
+                        }
+                    }
+                    _ => set.missing.push(arg.clone()),
+                }
+            } else {
+                set.missing.push(arg.clone());
+            }
+        }
+        set
+    }
+
+    /// 依名稱排序列出檔案，略過隱藏檔與隱藏目錄。
+    fn walk(root: &Path, max_depth: usize) -> Vec<PathBuf> {
+        walkdir::WalkDir::new(root)
+            .max_depth(max_depth)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .map(walkdir::DirEntry::into_path)
+            .collect()
+    }
+
+    /// 自萬用字元前的固定路徑開始掃描；`**` 或 `recursive` 時進入子目錄。
+    /// 回傳掃描根目錄與符合的檔案。
+    fn expand_glob(pattern: &str, recursive: bool) -> Option<(PathBuf, Vec<PathBuf>)> {
+        let matcher = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .ok()?
+            .compile_matcher();
+        let pat = Path::new(pattern);
+        let mut base = PathBuf::new();
+        let mut rest = 0;
+        for comp in pat.components() {
+            let c = comp.as_os_str().to_string_lossy();
+            if rest > 0 || c.contains(['*', '?', '[']) {
+                rest += 1;
+            } else {
+                base.push(comp);
+            }
+        }
+        let depth = if recursive || pattern.contains("**") {
+            usize::MAX
+        } else {
+            rest
+        };
+        let root = if base.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            base.clone()
+        };
+        let files = walk(&root, depth)
+            .into_iter()
+            .map(|p| {
+                // 以 "." 掃描時去掉前綴，讓 `*.srt` 能比對 `a.srt`
+                if base.as_os_str().is_empty() {
+                    p.strip_prefix(".").map(Path::to_path_buf).unwrap_or(p)
+                } else {
+                    p
+                }
+            })
+            .filter(|p| matcher.is_match(p))
+            .collect();
+        Some((base, files))
+    }
+
+    /// `output_template` 的預設值
+    pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{name}.txt";
+
+    /// 轉換格式 (`--to`) 時的輸出模板：{name}、{ext} 改用目標格式的副檔名，
+    /// 預設模板不再多加 `.txt`，例如 `e1.srt` 輸出 `e1.sbv`。
+    pub fn retarget_template(template: &str, to: Format) -> String {
+        let ext = to.extension();
+        let template = if template == DEFAULT_OUTPUT_TEMPLATE {
+            "{stem}.{ext}"
+        } else {
+            template
+        };
+        template
+            .replace("{name}", &format!("{{stem}}.{}", ext))
+            .replace("{ext}", ext)
+    }
+
+    /// 依模板產生輸出路徑。模板可用 {name} (完整檔名)、{stem} (去掉最後一個副檔名)、
+    /// {ext} (最後一個副檔名，不含點) 與 `vars` 提供的其他欄位，例如 {profile}、{conversion}。
+    /// `to` 與輸入副檔名不同時改用 `retarget_template`。
+    /// 指定 `out_dir` 時依 `rel` 保留子目錄結構，否則輸出到輸入檔旁。
+    pub fn output_path(
+        input: &InputFile,
+        template: &str,
+        to: Option<Format>,
+        out_dir: Option<&Path>,
+        vars: &[(&str, &str)],
+    ) -> PathBuf {
+        let name = input
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((s, e)) if !s.is_empty() => (s.to_string(), e.to_string()),
+            _ => (name.clone(), String::new()),
+        };
+        let template = match to {
+            Some(f) if !f.extension().eq_ignore_ascii_case(&ext) => retarget_template(template, f),
+            _ => template.to_string(),
+        };
+        let mut file = template
+            .replace("{name}", &name)
+            .replace("{stem}", &stem)
+            .replace("{ext}", &ext);
+        for (k, v) in vars {
+            file = file.replace(&format!("{{{}}}", k), v);
+        }
+        // 空欄位留下的 `..` 或結尾的點一併收掉
+        while file.contains("..") {
+            file = file.replace("..", ".");
+        }
+        let file = file.trim_matches('.').to_string();
+        let dir = match out_dir {
+            Some(d) => d.join(input.rel.parent().unwrap_or(Path::new(""))),
+            None => input
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+        };
+        dir.join(file)
+    }
+
+    static OUTPUT_FIELD_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\{(name|stem|ext)\}").expect("內建樣式"));
+
+    /// 輸出檔名模板的反向比對：檔名符合模板，且可推回的來源檔存在於同一目錄
+    /// (不是自己) 時，視為此模板的產物。例如 `{stem}.zh-Hant.{ext}` 下，
+    /// 有 `e1.srt` 時 `e1.zh-Hant.srt` 為成果檔；只有 `notes.txt` 時 `{name}.txt` 不會誤判。
+    pub struct OutputMatcher {
+        re: Regex,
+    }
+
+    impl OutputMatcher {
+        /// `vars` 與 `output_path` 相同；模板無法編成樣式時回傳 `None`。
+        pub fn new(template: &str, vars: &[(&str, &str)]) -> Option<Self> {
+            let mut t = template.to_string();
+            for (k, v) in vars {
+                t = t.replace(&format!("{{{}}}", k), v);
+            }
+            while t.contains("..") {
+                t = t.replace("..", ".");
+            }
+            let t = t.trim_matches('.');
+            let mut pattern = String::from("^");
+            let mut seen = Vec::new();
+            let mut last = 0;
+            for m in OUTPUT_FIELD_RE.find_iter(t) {
+                pattern.push_str(&regex::escape(&t[last..m.start()]));
+                let field = &t[m.start() + 1..m.end() - 1];
+                let body = if field == "ext" { "[^.]*" } else { ".+" };
+                if seen.contains(&field) {
+                    pattern.push_str(&format!("(?:{})", body));
+                } else {
+                    pattern.push_str(&format!("(?P<{}>{})", field, body));
+                    seen.push(field);
+                }
+                last = m.end();
+            }
+            pattern.push_str(&regex::escape(&t[last..]));
+            pattern.push('$');
+            Regex::new(&pattern).ok().map(|re| Self { re })
+        }
+
+        pub fn is_output(&self, path: &Path) -> bool {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                return false;
+            };
+            let Some(c) = self.re.captures(&name) else {
+                return false;
+            };
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let is_source = |n: &str| n != name && dir.join(n).is_file();
+            match (c.name("name"), c.name("stem"), c.name("ext")) {
+                (Some(n), _, _) => is_source(n.as_str()),
+                (None, Some(s), Some(e)) if e.as_str().is_empty() => is_source(s.as_str()),
+                (None, Some(s), Some(e)) => is_source(&format!("{}.{}", s.as_str(), e.as_str())),
+                // 只有 {stem}：同目錄下任一主檔名相同的檔案
+                (None, Some(s), None) => fs::read_dir(dir).is_ok_and(|entries| {
+                    entries.filter_map(|e| e.ok()).any(|e| {
+                        let n = e.file_name().to_string_lossy().to_string();
+                        n.rsplit_once('.').is_some_and(|(st, _)| st == s.as_str()) && is_source(&n)
+                    })
+                }),
+                // 固定檔名的模板：符合即為成果
+                (None, None, _) => true,
+            }
+        }
+    }
+
     // --- [ 功能塊: 翻譯核心 ] ---
+    // 固定樣式，只編譯一次；RawGuard 以複本持有
+    static TAG_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(\\N|\\h|\{.*?\}|<.*?>)").expect("內建樣式"));
+    static SECTION_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\[.*\]$").expect("內建樣式"));
+    /// LRC 行時間標籤 `[mm:ss.xx]` 與逐字時間標籤 `<mm:ss.xx>`
+    static LRC_TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\[\d{1,3}:\d{2}(?:[.:]\d{1,3})?\]|<\d{1,3}:\d{2}(?:[.:]\d{1,3})?>")
+            .expect("內建樣式")
+    });
+    /// LRC ID 標籤 `[ti:標題]`：前綴、值、結尾
+    static LRC_ID_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\s*\[[A-Za-z#]+:)(.*)(\]\s*)$").expect("內建樣式"));
+    /// 值為文字、可選擇轉換的 LRC ID 標籤；`offset`、`length` 等數值標籤一律不動
+    const LRC_TEXT_TAGS: &[&str] = &["ti", "ar", "al", "au", "lr", "by", "#"];
+
     pub struct RawGuard {
         pub tag_re: Regex,
         pub section_re: Regex,
+        pub lrc_time_re: Regex,
+        pub lrc_id_re: Regex,
     }
     impl Default for RawGuard {
         fn default() -> Self {
@@ -158,8 +1436,10 @@ pub mod core {
     impl RawGuard {
         pub fn new() -> Self {
             Self {
-                tag_re: Regex::new(r"(\\N|\\h|\{.*?\}|<.*?>)").unwrap(),
-                section_re: Regex::new(r"^\[.*\]$").unwrap(),
+                tag_re: TAG_RE.clone(),
+                section_re: SECTION_RE.clone(),
+                lrc_time_re: LRC_TIME_RE.clone(),
+                lrc_id_re: LRC_ID_RE.clone(),
             }
         }
         pub fn is_forbidden_zone(&self, l: &str, s: &str) -> bool {
@@ -188,51 +1468,651 @@ pub mod core {
         input: &str,
         output: &str,
         fix: bool,
-    ) -> io::Result<Vec<(usize, String, String)>> {
-        let conv = OpenCC::new(if p_mode {
+    ) -> Result<Vec<(usize, String, String)>> {
+        let mode = if p_mode {
             DefaultConfig::S2TWP
         } else {
             DefaultConfig::S2T
-        })
-        .unwrap();
-        let guard = RawGuard::new();
-        let raw_bytes = fs::read(input)?;
-        let mut detector = EncodingDetector::new();
-        detector.feed(&raw_bytes, true);
-        let encoding = detector.guess(None, true);
-        let (content, _, _) = encoding.decode(&raw_bytes);
-        let mut writer = File::create(output)?;
-        let mut pairs = Vec::new();
-        let mut section = String::new();
-        for (i, line) in content.lines().enumerate() {
-            let l = line.replace('\u{feff}', "").trim_end().to_string();
-            if guard.section_re.is_match(l.trim()) {
-                section = l.trim().to_string();
-            }
-            let trans = translate_single_line(&conv, &guard, &l, &section);
-            pairs.push((i + 1, l, trans.clone()));
-            writeln!(writer, "{}", trans)?;
-        }
-        if fix {
-            writeln!(writer)?;
-        }
-        Ok(pairs)
+        };
+        run_safe_translate_with(mode, input, output, fix)
     }
 
-    pub fn translate_single_line(conv: &OpenCC, guard: &RawGuard, l: &str, s: &str) -> String {
-        if guard.is_forbidden_zone(l, s) || is_srt_structure(l) {
-            return l.to_string();
+    /// 同 `run_safe_translate`，但直接指定 OpenCC 轉換設定 (例如 profile 的 s2hk)。
+    pub fn run_safe_translate_with(
+        mode: DefaultConfig,
+        input: &str,
+        output: &str,
+        fix: bool,
+    ) -> Result<Vec<(usize, String, String)>> {
+        let opts = StreamOptions {
+            fix_trailing_newline: fix,
+            diagnose: false,
+            collect: Collect::All,
+            line_ending: LineEnding::Preserve,
+            bom: Bom::Preserve,
+            input_encoding: None,
+            output_encoding: encoding::OutputEncoding::Utf8,
+            repair_mojibake: false,
+            format: None,
+            output_format: None,
+            frame_rate: None,
+            lrc_metadata: false,
+        };
+        Ok(Converter::new(mode)?
+            .convert_file(input, output, &opts)?
+            .pairs)
+    }
+
+    /// 可重複使用的轉換器：OpenCC 實例與 RawGuard 正則只建立一次，
+    /// 可在多個執行緒間共用 (`&Converter` 為 `Sync`)。
+    pub struct Converter {
+        conv: OpenCC,
+        guard: RawGuard,
+    }
+
+    impl Converter {
+        pub fn new(mode: DefaultConfig) -> Result<Self> {
+            let conv = OpenCC::new(mode).map_err(|e| Error::OpenCc {
+                config: mode.get_file_name().to_string(),
+                message: e.to_string(),
+            })?;
+            Ok(Self {
+                conv,
+                guard: RawGuard::new(),
+            })
         }
-        if (l.starts_with("Dialogue:") || l.starts_with("Comment:")) && s == "[Events]" {
-            let (m, c) = guard.split_ass_line(l);
-            let mut last = 0;
-            let mut res = String::new();
-            for cap in guard.tag_re.find_iter(c) {
-                res.push_str(&conv.convert(&c[last..cap.start()]));
-                res.push_str(cap.as_str());
-                last = cap.end();
-            }
-            res.push_str(&conv.convert(&c[last..]));
+
+        /// 轉換單行；`section` 為目前所在的 ASS 區段 (非 ASS 傳空字串)。
+        pub fn convert_line(&self, line: &str, section: &str) -> String {
+            translate_single_line(&self.conv, &self.guard, line, section)
+        }
+
+        /// 轉換純文字：不套用字幕結構判斷與標籤保護，`{}`、`<>` 與數字行照常轉換。
+        pub fn convert_text(&self, text: &str) -> String {
+            self.conv.convert(text)
+        }
+
+        /// 轉換一行 Markdown；`fence` 為目前所在的圍欄程式碼區塊 (見 `markdown::convert_line`)。
+        pub fn convert_markdown_line(&self, line: &str, fence: &mut String) -> String {
+            markdown::convert_line(line, fence, |t| self.conv.convert(t))
+        }
+
+        /// 轉換一行 LRC 歌詞；`metadata` 為 `true` 時一併轉換 `[ti:]`、`[ar:]` 等標籤的值。
+        pub fn convert_lrc_line(&self, line: &str, metadata: bool) -> String {
+            translate_lrc_line(&self.conv, &self.guard, line, metadata)
+        }
+
+        /// 轉換檔案；對照行依 `opts.collect` 保留。
+        pub fn convert_file(
+            &self,
+            input: &str,
+            output: &str,
+            opts: &StreamOptions,
+        ) -> Result<StreamSummary> {
+            let reader = File::open(input).map_err(|e| Error::io_at(input, e))?;
+            let file = File::create(output).map_err(|e| Error::io_at(output, e))?;
+            let mut writer = io::BufWriter::new(file);
+            let summary = self.stream(reader, &mut writer, opts, Some(Path::new(input)))?;
+            writer.flush().map_err(|e| Error::io_at(output, e))?;
+            Ok(summary)
+        }
+
+        /// 串流轉換：以開頭 (最多 `DETECT_PREFIX` 位元組，見 `read_prefix`) 判斷編碼、BOM、
+        /// 換行風格與格式，其後逐塊解碼、逐行輸出，每塊處理完即 flush，`tail -f | cw` 可即時看到結果；
+        /// 記憶體用量只與區塊大小和最長一行有關。輸出依 `opts.output_encoding` 編碼，
+        /// 換行與 BOM 預設沿用輸入，可由 `opts` 強制指定。未指定格式時依開頭內容判斷，
+        /// 看不出字幕結構的輸入 (例如管道輸入的筆記) 以純文字轉換。
+        ///
+        /// `opts.diagnose` 時邊讀邊診斷，輸入的 SHA-256 也在讀取時計算，不另外整份讀入。
+        /// 只有兩種情況需要整份輸入在記憶體中：TTML (XML 無法逐行處理) 與
+        /// 輸出格式不同於輸入的轉換 (`opts.output_format`，需讀出全部時間軸再重寫)。
+        pub fn convert_stream<R: Read, W: Write>(
+            &self,
+            input: R,
+            output: W,
+            opts: &StreamOptions,
+        ) -> Result<StreamSummary> {
+            self.stream(input, output, opts, None)
+        }
+
+        /// `convert_stream` 的本體；`path` 為輸入檔路徑，內容看不出格式時參考副檔名。
+        fn stream<R: Read, W: Write>(
+            &self,
+            input: R,
+            output: W,
+            opts: &StreamOptions,
+            path: Option<&Path>,
+        ) -> Result<StreamSummary> {
+            let mut input = HashingReader {
+                inner: input,
+                hasher: Sha256::new(),
+            };
+            let prefix = read_prefix(&mut input)?;
+            let detected = encoding::detect(&prefix);
+            // 未指定格式時依開頭內容判斷，其次副檔名，都看不出字幕結構即為純文字
+            let format = opts.format.unwrap_or_else(|| {
+                let enc = opts.input_encoding.unwrap_or(detected.encoding);
+                Format::sniff(&enc.decode_with_bom_removal(&prefix).0)
+                    .or_else(|| path.and_then(Format::from_path))
+                    .unwrap_or(Format::Plain)
+            });
+            if format == Format::Ttml || opts.output_format.is_some_and(|to| to != format) {
+                let opts = StreamOptions {
+                    format: Some(format),
+                    ..*opts
+                };
+                // 整份讀入的路徑以 `dyn Read` 接手，避免泛型遞迴展開
+                let mut input = prefix.as_slice().chain(input);
+                return if format == Format::Ttml {
+                    self.convert_ttml(&mut input, output, &opts)
+                } else {
+                    self.convert_reformat(&mut input, output, &opts)
+                };
+            }
+            let (encoding, mut decoder) = match opts.input_encoding {
+                // 指定編碼時仍移除同編碼的 BOM
+                Some(enc) => (enc, enc.new_decoder_with_bom_removal()),
+                None => (detected.encoding, detected.encoding.new_decoder()),
+            };
+            let bom = match opts.bom {
+                Bom::Preserve => detected.bom,
+                Bom::Always => true,
+                Bom::Never => false,
+            };
+            let mut sink = Sink {
+                out: output,
+                encoder: encoding::TextEncoder::new(opts.output_encoding),
+                buf: Vec::new(),
+            };
+            if bom {
+                sink.out.write_all(opts.output_encoding.bom())?;
+            }
+            let mut summary = StreamSummary {
+                encoding,
+                confidence: if opts.input_encoding.is_some() {
+                    encoding::Confidence::High
+                } else {
+                    detected.confidence
+                },
+                unmappable: Vec::new(),
+                unmappable_count: 0,
+                repaired: Vec::new(),
+                lines: 0,
+                changed: 0,
+                pairs: Vec::new(),
+                crlf: matches!(opts.line_ending, LineEnding::CrLf),
+                bom,
+                format,
+                issues: Vec::new(),
+                input_sha256: String::new(),
+                fixed_trailing_newline: false,
+            };
+            let mut diag = opts
+                .diagnose
+                .then(|| Diagnoser::new(format, encoding, opts.repair_mojibake));
+            let mut tail = TailCheck::default();
+            let mut eol_known = !matches!(opts.line_ending, LineEnding::Preserve);
+            let mut section = String::new();
+            let mut pending = String::new();
+            let mut chunk = prefix;
+            let mut buf = vec![0u8; STREAM_CHUNK];
+            loop {
+                let last = chunk.is_empty();
+                let base = summary.lines + 1;
+                decode_into(&mut decoder, &chunk, &mut pending, last, |n| {
+                    if let Some(d) = diag.as_mut() {
+                        d.malformed(base + n);
+                    }
+                });
+                if !eol_known {
+                    // 以第一個換行判斷整檔風格
+                    if let Some(pos) = pending.find('\n') {
+                        summary.crlf = pending[..pos].ends_with('\r');
+                        eol_known = true;
+                    }
+                }
+                let mut consumed = 0;
+                while let Some(pos) = pending[consumed..].find('\n') {
+                    let line = &pending[consumed..consumed + pos];
+                    tail.line(line);
+                    if let Some(d) = diag.as_mut() {
+                        d.line(line);
+                    }
+                    self.stream_line(line, format, &mut section, &mut sink, opts, &mut summary)?;
+                    consumed += pos + 1;
+                }
+                pending.drain(..consumed);
+                if last {
+                    break;
+                }
+                sink.out.flush()?;
+                let n = loop {
+                    match input.read(&mut buf) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        r => break r?,
+                    }
+                };
+                chunk.clear();
+                chunk.extend_from_slice(&buf[..n]);
+            }
+            // 剩下的是沒有換行結尾的最後一行
+            let ended = pending.is_empty();
+            if !ended {
+                let line = std::mem::take(&mut pending);
+                tail.line(&line);
+                if let Some(d) = diag.as_mut() {
+                    d.line(&line);
+                }
+                self.stream_line(&line, format, &mut section, &mut sink, opts, &mut summary)?;
+            }
+            if opts.fix_trailing_newline && format.is_srt() && tail.needs_fix(ended) {
+                sink.write(summary.eol(), 0, &mut summary)?;
+                summary.fixed_trailing_newline = true;
+            }
+            summary.issues = diag.map(|d| d.finish(ended)).unwrap_or_default();
+            summary.input_sha256 = hex(&input.hasher.finalize());
+            Ok(summary)
+        }
+
+        /// 轉換輸出格式：先逐行轉換文字，再讀出時間軸以目標格式重寫。
+        /// 需要整份文字在記憶體中；換行、BOM 與輸出字集仍依 `opts`。
+        fn convert_reformat<W: Write>(
+            &self,
+            input: &mut dyn Read,
+            output: W,
+            opts: &StreamOptions,
+        ) -> Result<StreamSummary> {
+            // 中間結果為 UTF-8；BOM 的判斷沿用 opts，寫出時再換成輸出字集的 BOM
+            let inner = StreamOptions {
+                output_encoding: encoding::OutputEncoding::Utf8,
+                output_format: None,
+                ..*opts
+            };
+            let mut buf = Vec::new();
+            let mut summary = self.convert_stream(input, &mut buf, &inner)?;
+            let text = String::from_utf8_lossy(&buf);
+            let text = text.trim_start_matches('\u{feff}');
+            let from = opts.format.unwrap_or_else(|| Format::detect(text));
+            let to = opts.output_format.unwrap_or(from);
+            let rendered = if from == to {
+                // 偵測後才知道格式相同：不重寫，保留原有排版
+                text.to_string()
+            } else {
+                let fps = subtitle::frame_rate(text, opts.frame_rate);
+                let cues = subtitle::parse(text, from, Some(fps))?;
+                let out = subtitle::render(&cues, to, fps)?;
+                if summary.crlf {
+                    out.replace('\n', "\r\n")
+                } else {
+                    out
+                }
+            };
+            let mut sink = Sink {
+                out: output,
+                encoder: encoding::TextEncoder::new(opts.output_encoding),
+                buf: Vec::new(),
+            };
+            if summary.bom {
+                sink.out.write_all(opts.output_encoding.bom())?;
+            }
+            for (idx, line) in rendered.split_inclusive('\n').enumerate() {
+                sink.write(line, idx + 1, &mut summary)?;
+            }
+            Ok(summary)
+        }
+
+        /// TTML 為 XML，樣式、區域與屬性值 (例如 `tts:fontFamily`) 不可轉換：
+        /// 整份讀入後只轉換 `<body>` 內的文字節點，不逐行處理。
+        fn convert_ttml<W: Write>(
+            &self,
+            input: &mut dyn Read,
+            output: W,
+            opts: &StreamOptions,
+        ) -> Result<StreamSummary> {
+            let mut raw = Vec::new();
+            input.read_to_end(&mut raw)?;
+            let decoded = encoding::decode(&raw, opts.input_encoding);
+            let input_sha256 = sha256_hex(&raw);
+            drop(raw);
+            let text = decoded.text.as_str();
+            let converted = ttml::convert_text_nodes(text, |t| self.conv.convert(t))?;
+            let crlf = match opts.line_ending {
+                LineEnding::Preserve => text
+                    .find('\n')
+                    .is_some_and(|pos| text[..pos].ends_with('\r')),
+                LineEnding::CrLf => true,
+                LineEnding::Lf => false,
+            };
+            let mut summary = StreamSummary {
+                encoding: decoded.detection.encoding,
+                confidence: decoded.detection.confidence,
+                unmappable: Vec::new(),
+                unmappable_count: 0,
+                repaired: Vec::new(),
+                lines: 0,
+                changed: 0,
+                pairs: Vec::new(),
+                crlf,
+                bom: match opts.bom {
+                    Bom::Preserve => decoded.detection.bom,
+                    Bom::Always => true,
+                    Bom::Never => false,
+                },
+                format: Format::Ttml,
+                issues: if opts.diagnose {
+                    diagnose_as(&decoded, Format::Ttml)
+                } else {
+                    Vec::new()
+                },
+                input_sha256,
+                fixed_trailing_newline: false,
+            };
+            // 轉換不增減換行，原文與譯文可逐行對照；檔頭不列入對照
+            let body = ttml::body_line(text);
+            for (l, trans) in text.lines().zip(converted.lines()) {
+                summary.lines += 1;
+                if trans != l {
+                    summary.changed += 1;
+                }
+                let keep = match opts.collect {
+                    Collect::None => false,
+                    Collect::Head(n) => summary.pairs.len() < n,
+                    Collect::All => true,
+                };
+                if keep && summary.lines >= body {
+                    summary
+                        .pairs
+                        .push((summary.lines, l.to_string(), trans.to_string()));
+                }
+            }
+            let to = opts.output_format.unwrap_or(Format::Ttml);
+            let mut rendered = if to == Format::Ttml {
+                converted
+            } else {
+                let fps = opts.frame_rate.unwrap_or(subtitle::DEFAULT_FPS);
+                subtitle::render(&ttml::parse(&converted)?, to, fps)?
+            };
+            // 換行風格依 opts 統一
+            rendered = rendered.replace("\r\n", "\n");
+            if summary.crlf {
+                rendered = rendered.replace('\n', "\r\n");
+            }
+            let mut sink = Sink {
+                out: output,
+                encoder: encoding::TextEncoder::new(opts.output_encoding),
+                buf: Vec::new(),
+            };
+            if summary.bom {
+                sink.out.write_all(opts.output_encoding.bom())?;
+            }
+            for (idx, line) in rendered.split_inclusive('\n').enumerate() {
+                sink.write(line, idx + 1, &mut summary)?;
+            }
+            Ok(summary)
+        }
+
+        fn stream_line<W: Write>(
+            &self,
+            line: &str,
+            format: Format,
+            section: &mut String,
+            sink: &mut Sink<W>,
+            opts: &StreamOptions,
+            summary: &mut StreamSummary,
+        ) -> Result<()> {
+            let mut l = line.replace('\u{feff}', "").trim_end().to_string();
+            if opts.repair_mojibake {
+                if let Some((fixed, chains)) = mojibake::repair_line(&l) {
+                    if summary.repaired.len() < MAX_UNMAPPABLE {
+                        summary.repaired.push((summary.lines + 1, chains));
+                    }
+                    l = fixed;
+                }
+            }
+            // `section` 依格式保存跨行狀態：ASS 區段或 Markdown 圍欄
+            let trans = match format {
+                Format::Lrc => self.convert_lrc_line(&l, opts.lrc_metadata),
+                Format::Plain => self.convert_text(&l),
+                Format::Markdown => self.convert_markdown_line(&l, section),
+                _ => {
+                    if self.guard.section_re.is_match(l.trim()) {
+                        *section = l.trim().to_string();
+                    }
+                    self.convert_line(&l, section)
+                }
+            };
+            summary.lines += 1;
+            sink.write(&trans, summary.lines, summary)?;
+            sink.write(summary.eol(), summary.lines, summary)?;
+            if trans != l {
+                summary.changed += 1;
+            }
+            let keep = match opts.collect {
+                Collect::None => false,
+                Collect::Head(n) => summary.pairs.len() < n,
+                Collect::All => true,
+            };
+            if keep {
+                summary.pairs.push((summary.lines, l, trans));
+            }
+            Ok(())
+        }
+    }
+
+    /// 編碼後寫出；記錄目標字集無法表示的字元。
+    struct Sink<W> {
+        out: W,
+        encoder: encoding::TextEncoder,
+        buf: Vec<u8>,
+    }
+
+    impl<W: Write> Sink<W> {
+        fn write(
+            &mut self,
+            text: &str,
+            line: usize,
+            summary: &mut StreamSummary,
+        ) -> io::Result<()> {
+            self.buf.clear();
+            self.encoder.encode(text, &mut self.buf, |c| {
+                summary.unmappable_count += 1;
+                if summary.unmappable.len() < MAX_UNMAPPABLE {
+                    summary.unmappable.push((line, c));
+                }
+            });
+            self.out.write_all(&self.buf)
+        }
+    }
+
+    /// 讀取判斷用的開頭：讀滿 `DETECT_PREFIX`、讀到結尾，或已有完整一行且輸入暫無更多資料
+    /// (一次讀取未填滿，管道常見) 時停止。檔案一次即可讀滿；互動式管道不必等滿 64KB 才開始輸出，
+    /// 代價是編碼只依已到達的內容判斷。
+    fn read_prefix<R: Read>(input: &mut R) -> io::Result<Vec<u8>> {
+        let mut prefix = vec![0u8; DETECT_PREFIX];
+        let mut len = 0;
+        while len < DETECT_PREFIX {
+            let want = DETECT_PREFIX - len;
+            let n = match input.read(&mut prefix[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                r => r?,
+            };
+            len += n;
+            if n == 0 || (n < want && prefix[..len].contains(&b'\n')) {
+                break;
+            }
+        }
+        prefix.truncate(len);
+        Ok(prefix)
+    }
+
+    /// 無法表示字元與修復亂碼行最多保留幾筆明細
+    pub const MAX_UNMAPPABLE: usize = 200;
+
+    /// 串流時偵測編碼所用的開頭長度
+    pub const DETECT_PREFIX: usize = 64 * 1024;
+    const STREAM_CHUNK: usize = 64 * 1024;
+
+    /// 串流轉換時保留哪些行的對照。
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Collect {
+        None,
+        /// 只保留前 N 行，供預覽使用
+        Head(usize),
+        All,
+    }
+
+    /// 輸出換行風格；`Preserve` 沿用輸入第一個換行的風格 (無換行時為 LF)。
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum LineEnding {
+        #[default]
+        Preserve,
+        Lf,
+        CrLf,
+    }
+
+    /// 輸出 BOM；`Preserve` 在輸入帶 BOM 時寫入輸出字集的 BOM (GBK / Big5 無 BOM)。
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum Bom {
+        #[default]
+        Preserve,
+        Always,
+        Never,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct StreamOptions {
+        /// SRT 缺少檔尾規範空行時補上 (其他格式不受影響)
+        pub fix_trailing_newline: bool,
+        /// 邊讀邊診斷輸入，結果放在 `StreamSummary::issues`
+        pub diagnose: bool,
+        pub collect: Collect,
+        pub line_ending: LineEnding,
+        pub bom: Bom,
+        /// 指定輸入編碼；`None` 表示自動偵測
+        pub input_encoding: Option<&'static encoding_rs::Encoding>,
+        pub output_encoding: encoding::OutputEncoding,
+        /// 轉換前嘗試反轉重複解碼的亂碼行
+        pub repair_mojibake: bool,
+        /// 輸入格式；`None` 依開頭內容判斷 (Markdown 需明確指定)
+        pub format: Option<Format>,
+        /// 輸出格式；`None` 或與輸入相同時逐行串流，不經時間軸重寫
+        pub output_format: Option<Format>,
+        /// MicroDVD 影格率；`None` 取檔頭或 `subtitle::DEFAULT_FPS`
+        pub frame_rate: Option<f64>,
+        /// LRC 一併轉換 `[ti:]`、`[ar:]` 等標籤的值
+        pub lrc_metadata: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct StreamSummary {
+        /// 已修復的亂碼行 (行號, 反轉的編碼鏈)，最多 `MAX_UNMAPPABLE` 筆
+        pub repaired: Vec<(usize, Vec<mojibake::Chain>)>,
+        /// 實際用於解碼的輸入編碼
+        pub encoding: &'static encoding_rs::Encoding,
+        pub confidence: encoding::Confidence,
+        /// 目標字集無法表示而被取代的字元 (行號, 字元)，最多 `MAX_UNMAPPABLE` 筆
+        pub unmappable: Vec<(usize, char)>,
+        pub unmappable_count: usize,
+        pub lines: usize,
+        pub changed: usize,
+        pub pairs: Vec<(usize, String, String)>,
+        /// 實際輸出的換行是否為 CRLF
+        pub crlf: bool,
+        /// 實際輸出是否帶 BOM
+        pub bom: bool,
+        /// 實際採用的輸入格式
+        pub format: Format,
+        /// 輸入的診斷結果 (`opts.diagnose` 時)
+        pub issues: Vec<SubtitleIssue>,
+        /// 輸入位元組的 SHA-256 (十六進位)
+        pub input_sha256: String,
+        /// 是否補上了 SRT 檔尾空行
+        pub fixed_trailing_newline: bool,
+    }
+
+    impl StreamSummary {
+        fn eol(&self) -> &'static str {
+            if self.crlf {
+                "\r\n"
+            } else {
+                "\n"
+            }
+        }
+    }
+
+    /// 解碼並附加到 `dst`；不符合編碼的位元組以 U+FFFD 取代，
+    /// 並以此前新增的換行數 (相對於 `dst` 原有內容) 呼叫 `malformed`。
+    fn decode_into(
+        decoder: &mut encoding_rs::Decoder,
+        src: &[u8],
+        dst: &mut String,
+        last: bool,
+        mut malformed: impl FnMut(usize),
+    ) {
+        let mut src = src;
+        let (mut counted, mut newlines) = (dst.len(), 0);
+        loop {
+            dst.reserve(
+                decoder
+                    .max_utf8_buffer_length_without_replacement(src.len())
+                    .unwrap_or(src.len() * 3 + 16),
+            );
+            let (res, read) = decoder.decode_to_string_without_replacement(src, dst, last);
+            src = &src[read..];
+            match res {
+                encoding_rs::DecoderResult::InputEmpty => break,
+                encoding_rs::DecoderResult::OutputFull => {}
+                encoding_rs::DecoderResult::Malformed(..) => {
+                    newlines += dst[counted..].matches('\n').count();
+                    counted = dst.len();
+                    malformed(newlines);
+                    dst.push('\u{FFFD}');
+                }
+            }
+        }
+    }
+
+    /// 讀取時一併計算 SHA-256，稽核日誌不必另外整份讀入輸入。
+    struct HashingReader<R> {
+        inner: R,
+        hasher: Sha256,
+    }
+
+    impl<R: Read> Read for HashingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.hasher.update(&buf[..n]);
+            Ok(n)
+        }
+    }
+
+    pub fn detect_encoding(raw: &[u8]) -> &'static encoding_rs::Encoding {
+        encoding::detect(raw).encoding
+    }
+
+    /// 只讀開頭 (見 `read_prefix`) 判斷檔案編碼。
+    pub fn detect_file_encoding<P: AsRef<Path>>(path: P) -> Result<&'static encoding_rs::Encoding> {
+        let path = path.as_ref();
+        let prefix = File::open(path)
+            .and_then(|mut file| read_prefix(&mut file))
+            .map_err(|e| Error::io_at(path, e))?;
+        Ok(detect_encoding(&prefix))
+    }
+
+    pub fn translate_single_line(conv: &OpenCC, guard: &RawGuard, l: &str, s: &str) -> String {
+        if guard.is_forbidden_zone(l, s) || is_srt_structure(l) {
+            return l.to_string();
+        }
+        if (l.starts_with("Dialogue:") || l.starts_with("Comment:")) && s == "[Events]" {
+            let (m, c) = guard.split_ass_line(l);
+            let mut last = 0;
+            let mut res = String::new();
+            for cap in guard.tag_re.find_iter(c) {
+                res.push_str(&conv.convert(&c[last..cap.start()]));
+                res.push_str(cap.as_str());
+                last = cap.end();
+            }
+            res.push_str(&conv.convert(&c[last..]));
             return format!("{}{}", m, res);
         }
         let mut last = 0;
@@ -246,50 +2126,372 @@ pub mod core {
         res
     }
 
+    /// LRC 歌詞：時間標籤與逐字時間標籤原樣保留，只轉換歌詞文字；
+    /// ID 標籤只在 `metadata` 時轉換文字類的值 (`LRC_TEXT_TAGS`)。
+    pub fn translate_lrc_line(conv: &OpenCC, guard: &RawGuard, l: &str, metadata: bool) -> String {
+        if let Some(c) = guard.lrc_id_re.captures(l) {
+            let key = c[1].trim().trim_start_matches('[').trim_end_matches(':');
+            if !(metadata && LRC_TEXT_TAGS.iter().any(|t| t.eq_ignore_ascii_case(key))) {
+                return l.to_string();
+            }
+            return format!("{}{}{}", &c[1], conv.convert(&c[2]), &c[3]);
+        }
+        let mut last = 0;
+        let mut res = String::new();
+        for cap in guard.lrc_time_re.find_iter(l) {
+            res.push_str(&conv.convert(&l[last..cap.start()]));
+            res.push_str(cap.as_str());
+            last = cap.end();
+        }
+        res.push_str(&conv.convert(&l[last..]));
+        res
+    }
+
     // --- [ 功能塊: 診斷 ] ---
     pub fn is_srt_structure(l: &str) -> bool {
         let t = l.trim();
         t.is_empty() || t.contains("-->") || (t.chars().all(|c| c.is_ascii_digit()) && t.len() < 10)
     }
 
+    /// 診斷檔案；編碼與格式自動偵測。第二個參數不影響結果，僅為相容舊版呼叫而保留。
     pub fn diagnose_file(path: &str, _translate: bool) -> Vec<SubtitleIssue> {
-        let mut issues = Vec::new();
-        let content = fs::read_to_string(path)
-            .unwrap_or_default()
-            .replace('\u{feff}', "");
-        if path.to_lowercase().ends_with(".ass") {
-            return issues;
-        }
-        if needs_trailing_newline_fix(path) {
-            issues.push(SubtitleIssue {
+        diagnose_file_with(path, None, None)
+    }
+
+    /// 同 `diagnose_file`，但可指定輸入編碼與格式 (`None` 為自動偵測)。
+    pub fn diagnose_file_with(
+        path: &str,
+        input_encoding: Option<&'static encoding_rs::Encoding>,
+        format: Option<Format>,
+    ) -> Vec<SubtitleIssue> {
+        match encoding::read_file(path, input_encoding) {
+            Ok(decoded) => {
+                let format = format.unwrap_or_else(|| Format::resolve(path, &decoded.text));
+                diagnose_as(&decoded, format)
+            }
+            Err(e) => vec![SubtitleIssue {
                 line: 0,
-                message: "檔案末端損壞：缺少 SRT 規範空行".to_string(),
-            });
+                message: format!("無法讀取：{}", e),
+            }],
         }
-        if let Ok(srt) = skrt::Srt::try_parse(&content) {
-            for (idx, sub) in srt.subtitles().iter().enumerate() {
-                if sub.start() > sub.end() {
-                    issues.push(SubtitleIssue {
-                        line: idx + 1,
-                        message: "時間邏輯錯誤：結束早於開始".to_string(),
-                    });
+    }
+
+    /// 對已解碼的內容做診斷；格式依內容判斷，`path` 的副檔名只在內容看不出時參考。
+    pub fn diagnose_decoded(path: &str, decoded: &encoding::Decoded) -> Vec<SubtitleIssue> {
+        diagnose_as(decoded, Format::resolve(path, &decoded.text))
+    }
+
+    /// 依指定格式診斷；SRT 規範空行與時間軸檢查只套用在 SRT。
+    pub fn diagnose_as(decoded: &encoding::Decoded, format: Format) -> Vec<SubtitleIssue> {
+        let mut diag = Diagnoser::new(format, decoded.detection.encoding, false);
+        for &line in &decoded.malformed_lines {
+            diag.malformed(line);
+        }
+        let text = decoded.text.as_str();
+        for line in text.split_inclusive('\n') {
+            diag.line(line.strip_suffix('\n').unwrap_or(line));
+        }
+        let mut issues = diag.finish(text.ends_with('\n'));
+        if format == Format::Ttml {
+            issues.extend(diagnose_timeline(text, format));
+        }
+        issues
+    }
+
+    /// 逐行診斷：`diagnose_as` 與串流轉換共用，只保留尚未結束的一句字幕，
+    /// 記憶體與檔案大小無關。TTML 為 XML，時間軸檢查由 `diagnose_as` 整份處理。
+    pub struct Diagnoser {
+        format: Format,
+        encoding: &'static encoding_rs::Encoding,
+        /// 修復亂碼時可反轉的行不列入
+        skip_repairable: bool,
+        /// 尚未讀到的編碼異常行 (串流時解碼先於分行)
+        pending_malformed: std::collections::VecDeque<usize>,
+        malformed: Vec<usize>,
+        malformed_count: usize,
+        last_malformed: usize,
+        garbled: Vec<SubtitleIssue>,
+        garbled_count: usize,
+        timeline: Option<subtitle::CueReader>,
+        timeline_error: Option<Error>,
+        reversed: Vec<usize>,
+        cues: Vec<subtitle::Cue>,
+        tail: TailCheck,
+    }
+
+    impl Diagnoser {
+        pub fn new(
+            format: Format,
+            encoding: &'static encoding_rs::Encoding,
+            skip_repairable: bool,
+        ) -> Self {
+            let timeline = match format {
+                Format::Srt | Format::MicroDvd | Format::Sbv | Format::Sami => {
+                    subtitle::CueReader::new(format, None).ok()
                 }
+                _ => None,
+            };
+            Self {
+                format,
+                encoding,
+                skip_repairable,
+                pending_malformed: Default::default(),
+                malformed: Vec::new(),
+                malformed_count: 0,
+                last_malformed: 0,
+                garbled: Vec::new(),
+                garbled_count: 0,
+                timeline,
+                timeline_error: None,
+                reversed: Vec::new(),
+                cues: Vec::new(),
+                tail: TailCheck::default(),
             }
         }
-        issues
+
+        /// 第 `line` 行含有不符合編碼的位元組 (可在讀到該行之前回報)。
+        pub fn malformed(&mut self, line: usize) {
+            if line == self.last_malformed {
+                return;
+            }
+            self.last_malformed = line;
+            self.malformed_count += 1;
+            if self.malformed.len() < MAX_MALFORMED_REPORTED {
+                self.malformed.push(line);
+            }
+            self.pending_malformed.push_back(line);
+        }
+
+        /// 讀入下一行 (不含 `\n`)。
+        pub fn line(&mut self, raw: &str) {
+            self.tail.line(raw);
+            let n = self.tail.lines;
+            while self.pending_malformed.front().is_some_and(|&m| m < n) {
+                self.pending_malformed.pop_front();
+            }
+            let malformed = self.pending_malformed.front() == Some(&n);
+            let line = raw.strip_suffix('\r').unwrap_or(raw);
+            let message = match mojibake::scan_line(line) {
+                Some(mojibake::Finding::Mojibake { .. }) if self.skip_repairable => None,
+                Some(mojibake::Finding::Mojibake { chains, .. }) => Some(format!(
+                    "{}{}，可用 --repair-mojibake 修復",
+                    MOJIBAKE_PREFIX,
+                    chains
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" → ")
+                )),
+                // 解碼錯誤產生的取代字元已另行回報
+                Some(mojibake::Finding::Replacement) if !malformed => {
+                    Some("含有 U+FFFD 取代字元：原始文字已遺失".to_string())
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.garbled_count += 1;
+                if self.garbled.len() < MAX_MALFORMED_REPORTED {
+                    self.garbled.push(SubtitleIssue { line: n, message });
+                }
+            }
+            if self.timeline_error.is_none() {
+                if let Some(reader) = self.timeline.as_mut() {
+                    match reader.push(line, &mut self.cues) {
+                        Ok(()) => self.check_cues(),
+                        Err(e) => self.timeline_error = Some(e),
+                    }
+                }
+            }
+        }
+
+        fn check_cues(&mut self) {
+            self.reversed.extend(
+                self.cues
+                    .drain(..)
+                    .filter(|c| c.start_ms > c.end_ms)
+                    .map(|c| c.line),
+            );
+        }
+
+        /// 輸入結束；`ended_with_newline` 為最後一行是否以換行結尾。
+        pub fn finish(mut self, ended_with_newline: bool) -> Vec<SubtitleIssue> {
+            if self.timeline_error.is_none() {
+                if let Some(reader) = self.timeline.take() {
+                    match reader.finish(&mut self.cues) {
+                        Ok(()) => self.check_cues(),
+                        Err(e) => self.timeline_error = Some(e),
+                    }
+                }
+            }
+            let mut issues = Vec::new();
+            let name = self.encoding.name();
+            for line in &self.malformed {
+                issues.push(SubtitleIssue {
+                    line: *line,
+                    message: format!("編碼異常：含有不符合 {} 的位元組 (已以 U+FFFD 取代)", name),
+                });
+            }
+            if self.malformed_count > self.malformed.len() {
+                issues.push(SubtitleIssue {
+                    line: 0,
+                    message: format!(
+                        "編碼異常：另有 {} 行含有不符合 {} 的位元組",
+                        self.malformed_count - self.malformed.len(),
+                        name
+                    ),
+                });
+            }
+            issues.append(&mut self.garbled);
+            if self.garbled_count > MAX_MALFORMED_REPORTED {
+                issues.push(SubtitleIssue {
+                    line: 0,
+                    message: format!(
+                        "另有 {} 行疑似亂碼",
+                        self.garbled_count - MAX_MALFORMED_REPORTED
+                    ),
+                });
+            }
+            let reversed = self.reversed.iter().map(|&line| SubtitleIssue {
+                line,
+                message: "時間邏輯錯誤：結束早於開始".to_string(),
+            });
+            if !self.format.is_srt() {
+                match self.timeline_error {
+                    Some(Error::Parse { line, message, .. }) => issues.push(SubtitleIssue {
+                        line,
+                        message: format!("時間軸格式錯誤：{}", message),
+                    }),
+                    Some(e) => issues.push(SubtitleIssue {
+                        line: 0,
+                        message: e.to_string(),
+                    }),
+                    None => issues.extend(reversed),
+                }
+                return issues;
+            }
+            if self.tail.needs_fix(ended_with_newline) {
+                issues.push(SubtitleIssue {
+                    line: 0,
+                    message: "檔案末端損壞：缺少 SRT 規範空行".to_string(),
+                });
+            }
+            // SRT 結構錯誤不另行回報，只在能完整解析時檢查時間邏輯
+            if self.timeline_error.is_none() {
+                issues.extend(reversed);
+            }
+            issues
+        }
+    }
+
+    /// 逐行判斷檔尾是否缺少 SRT 規範空行 (與 `needs_trailing_newline` 相同規則)。
+    #[derive(Debug, Default)]
+    struct TailCheck {
+        lines: usize,
+        last_blank: bool,
+    }
+
+    impl TailCheck {
+        fn line(&mut self, raw: &str) {
+            self.lines += 1;
+            self.last_blank = raw
+                .replace('\u{feff}', "")
+                .trim_end_matches('\r')
+                .is_empty();
+        }
+
+        fn needs_fix(&self, ended_with_newline: bool) -> bool {
+            !(ended_with_newline && self.lines >= 2 && self.last_blank)
+        }
+    }
+
+    /// TTML 的時間軸檢查：無法解析的內容與結束早於開始的句子。
+    fn diagnose_timeline(text: &str, format: Format) -> Vec<SubtitleIssue> {
+        match subtitle::parse(text, format, None) {
+            Ok(cues) => cues
+                .iter()
+                .filter(|c| c.start_ms > c.end_ms)
+                .map(|c| SubtitleIssue {
+                    line: c.line,
+                    message: "時間邏輯錯誤：結束早於開始".to_string(),
+                })
+                .collect(),
+            Err(Error::Parse { line, message, .. }) => vec![SubtitleIssue {
+                line,
+                message: format!("時間軸格式錯誤：{}", message),
+            }],
+            Err(e) => vec![SubtitleIssue {
+                line: 0,
+                message: e.to_string(),
+            }],
+        }
     }
 
+    /// 編碼異常與亂碼最多逐行列出幾筆
+    const MAX_MALFORMED_REPORTED: usize = 20;
+    /// 亂碼診斷訊息的開頭，修復後據此移除對應異常
+    pub const MOJIBAKE_PREFIX: &str = "疑似亂碼：";
+
+    /// SRT 檔是否缺少檔尾空行；其他格式沒有這項規範，一律為 `false`。
     pub fn needs_trailing_newline_fix(path: &str) -> bool {
-        if let Ok(data) = fs::read(path) {
-            if data.is_empty() {
-                return true;
+        encoding::read_file(path, None).is_ok_and(|d| {
+            Format::resolve(path, &d.text).is_srt() && needs_trailing_newline(&d.text)
+        })
+    }
+
+    /// 檔尾是否缺少 SRT 規範的空行 (最後需有 `\n\n` 或 `\n\r\n`)。
+    pub fn needs_trailing_newline(text: &str) -> bool {
+        match text.strip_suffix('\n') {
+            None => true,
+            Some(rest) => !rest.trim_end_matches('\r').ends_with('\n'),
+        }
+    }
+
+    // --- [ 功能塊: 日誌 ] ---
+    /// 依 `log_level` 決定此筆紀錄是否寫入：成功為 INFO、有異常為 WARN、轉換失敗為 ERROR。
+    pub fn should_log(threshold: &str, status: &ResultStatus, issues: &[SubtitleIssue]) -> bool {
+        let rank = |l: &str| {
+            LOG_LEVELS
+                .iter()
+                .position(|x| x.eq_ignore_ascii_case(l))
+                .unwrap_or(2)
+        };
+        let level = match status {
+            ResultStatus::ConvertError => "ERROR",
+            ResultStatus::VerifWarning => "WARN",
+            ResultStatus::Success if !issues.is_empty() => "WARN",
+            ResultStatus::Success => "INFO",
+        };
+        rank(level) >= rank(threshold)
+    }
+
+    /// 超過 `max_mb` 時輪替：`x.log` → `x.log.1` → … → `x.log.N`，最舊的丟棄。
+    /// `max_mb` 為 0 表示不輪替。
+    pub fn rotate_log(log_p: &Path, max_mb: u64, count: u32) -> Result<()> {
+        rotate_log_files(log_p, max_mb, count).map_err(|e| Error::io_at(log_p, e))
+    }
+
+    fn rotate_log_files(log_p: &Path, max_mb: u64, count: u32) -> io::Result<()> {
+        let Ok(meta) = fs::metadata(log_p) else {
+            return Ok(());
+        };
+        if max_mb == 0 || meta.len() < max_mb * 1024 * 1024 {
+            return Ok(());
+        }
+        let backup = |n: u32| PathBuf::from(format!("{}.{}", log_p.display(), n));
+        if count == 0 {
+            return fs::remove_file(log_p);
+        }
+        let oldest = backup(count);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..count).rev() {
+            let from = backup(n);
+            if from.exists() {
+                fs::rename(&from, backup(n + 1))?;
             }
-            let len = data.len();
-            return len < 2
-                || data[len - 1] != b'\n'
-                || (data[len - 2] != b'\n' && data[len - 2] != b'\r');
         }
-        false
+        fs::rename(log_p, backup(1))
     }
 
     pub fn create_log(
@@ -297,33 +2499,75 @@ pub mod core {
         p_b: &str,
         log_p: &PathBuf,
         status: &ResultStatus,
-        _max: u64,
-        _count: u32,
+        max: u64,
+        count: u32,
         issues: &[SubtitleIssue],
-    ) -> io::Result<()> {
-        let mut f = OpenOptions::new().create(true).append(true).open(log_p)?;
+    ) -> Result<()> {
+        let at = |e| Error::io_at(log_p, e);
+        if let Some(dir) = log_p.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(at)?;
+        }
+        rotate_log(log_p, max, count)?;
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_p)
+            .map_err(at)?;
         writeln!(
             f,
             "\n批次：{} | 原檔：{} | 成果：{}",
             Local::now().format("%Y-%m-%d %H:%M:%S"),
             p_a,
             p_b
-        )?;
+        )
+        .map_err(at)?;
         for iss in issues {
-            writeln!(f, "🛠️ L{:03} {}", iss.line, iss.message)?;
+            writeln!(f, "🛠️ L{:03} {}", iss.line, iss.message).map_err(at)?;
         }
-        writeln!(f, "[ 狀態：{:?} ]\n{}", status, "-".repeat(40))?;
+        writeln!(f, "[ 狀態：{:?} ]\n{}", status, "-".repeat(40)).map_err(at)?;
         Ok(())
     }
 
+    pub fn sha256_hex(data: &[u8]) -> String {
+        hex(&Sha256::digest(data))
+    }
+
+    /// 逐塊計算檔案的 SHA-256，不整份讀入。
+    pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+        let path = path.as_ref();
+        let mut file = File::open(path).map_err(|e| Error::io_at(path, e))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(|e| Error::io_at(path, e))?;
+        Ok(hex(&hasher.finalize()))
+    }
+
+    fn hex(digest: &[u8]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// 附加一筆 JSON Lines 稽核紀錄，必要時建立目錄。
+    pub fn append_audit(path: &Path, record: &AuditRecord) -> Result<()> {
+        let at = |e| Error::io_at(path, e);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(at)?;
+        }
+        let line = serde_json::to_string(record).map_err(|e| at(io::Error::other(e)))?;
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(at)?;
+        writeln!(f, "{}", line).map_err(at)
+    }
+
     // --- [ 功能塊: 下載器 ] ---
     pub struct MegaDownloader;
     impl MegaDownloader {
-        pub fn scout_target(url: &str) -> Result<String, String> {
+        pub fn scout_target(url: &str) -> Result<String> {
             let output = Command::new("megals")
                 .arg(url)
                 .output()
-                .map_err(|e: io::Error| e.to_string())?;
+                .map_err(|e| Error::Network(format!("無法執行 megals: {}", e)))?;
             let list = String::from_utf8_lossy(&output.stdout);
             let mut candidates: Vec<String> = Vec::new();
             for line in list.lines() {
@@ -333,7 +2577,7 @@ pub mod core {
                 }
             }
             if candidates.is_empty() {
-                return Err("無字幕檔".to_string());
+                return Err(Error::Network("無字幕檔".to_string()));
             }
             Ok(candidates
                 .iter()
@@ -341,17 +2585,857 @@ pub mod core {
                 .unwrap_or(&candidates[0])
                 .clone())
         }
-        pub fn fetch_file(url: &str, target: &str, dest: &Path) -> Result<PathBuf, String> {
+        pub fn fetch_file(url: &str, target: &str, dest: &Path) -> Result<PathBuf> {
+            let name = Path::new(target)
+                .file_name()
+                .ok_or_else(|| Error::Network(format!("無效的檔名: {}", target)))?;
             let s = Command::new("megadl")
                 .arg("--path")
                 .arg(dest)
                 .arg(url)
                 .status()
-                .map_err(|e: io::Error| e.to_string())?;
+                .map_err(|e| Error::Network(format!("無法執行 megadl: {}", e)))?;
             if !s.success() {
-                return Err("下載失敗".to_string());
+                return Err(Error::Network("下載失敗".to_string()));
+            }
+            Ok(dest.join(name))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// 每個測試獨立的暫存目錄
+        fn scratch(name: &str) -> PathBuf {
+            let dir = env::temp_dir().join(format!("cw_core_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+
+        /// 寫入檔案，必要時建立上層目錄
+        fn write(path: &Path, text: &str) {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        fn touch(path: &Path) {
+            write(path, "1\n00:00:01,000 --> 00:00:02,000\n软件\n\n");
+        }
+
+        fn paths(set: &InputSet) -> Vec<PathBuf> {
+            set.files.iter().map(|f| f.path.clone()).collect()
+        }
+
+        #[test]
+        fn explicit_files_bypass_output_heuristics() {
+            let dir = scratch("explicit");
+            let out = dir.join("ou").join("g.srt.txt");
+            touch(&out);
+            let filter = InputFilter::new(false, vec!["srt".into()], &[], &["*.txt".into()])
+                .unwrap()
+                .skip_dir(&dir.join("ou"));
+            let arg = out.to_string_lossy().to_string();
+            let set = collect_inputs(&[arg], &filter);
+            assert_eq!(paths(&set), vec![out]);
+            assert!(set.skipped.is_empty());
+            // 以目錄展開時仍略過
+            let set = collect_inputs(&[dir.to_string_lossy().to_string()], &filter);
+            assert!(set.files.is_empty());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn output_matcher_requires_existing_source() {
+            let dir = scratch("matcher");
+            touch(&dir.join("e1.srt"));
+            touch(&dir.join("notes.txt"));
+            let m = OutputMatcher::new("{stem}.zh-Hant.{ext}", &[]).unwrap();
+            assert!(m.is_output(&dir.join("e1.zh-Hant.srt")));
+            assert!(!m.is_output(&dir.join("e2.zh-Hant.srt")));
+            assert!(!m.is_output(&dir.join("e1.srt")));
+            let m = OutputMatcher::new("{name}.txt", &[]).unwrap();
+            assert!(m.is_output(&dir.join("e1.srt.txt")));
+            assert!(!m.is_output(&dir.join("notes.txt")));
+            // 與輸入同名的模板不會把輸入本身當成果
+            let m = OutputMatcher::new("{name}", &[]).unwrap();
+            assert!(!m.is_output(&dir.join("e1.srt")));
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn output_matcher_follows_output_path() {
+            let dir = scratch("matcher_vars");
+            let input = InputFile {
+                path: dir.join("ep.ass"),
+                rel: PathBuf::from("ep.ass"),
+            };
+            touch(&input.path);
+            let vars = [("profile", ""), ("conversion", "s2twp")];
+            for tpl in [
+                "{stem}.{profile}.{conversion}.{ext}",
+                "{stem}.txt",
+                "{name}.{profile}.txt",
+            ] {
+                let out = output_path(&input, tpl, None, None, &vars);
+                let m = OutputMatcher::new(tpl, &vars).unwrap();
+                assert!(m.is_output(&out), "{} -> {}", tpl, out.display());
+            }
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn scanned_outputs_are_skipped() {
+            let dir = scratch("scanned");
+            touch(&dir.join("a.srt"));
+            touch(&dir.join("a.srt.txt"));
+            touch(&dir.join("b.srt.tmp"));
+            let filter = InputFilter::new(false, Vec::new(), &[], &[]).unwrap();
+            let set = collect_inputs(&[dir.to_string_lossy().to_string()], &filter);
+            assert_eq!(paths(&set), [dir.join("a.srt")]);
+            assert_eq!(set.skipped.len(), 2);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        /// 模擬管道：每次讀取只給一行，並記錄給出下一行前輸出端已有多少內容
+        struct Trickle {
+            lines: Vec<&'static str>,
+            out: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+            seen: Vec<usize>,
+        }
+
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.seen.push(self.out.borrow().len());
+                if self.lines.is_empty() {
+                    return Ok(0);
+                }
+                let line = self.lines.remove(0).as_bytes();
+                buf[..line.len()].copy_from_slice(line);
+                Ok(line.len())
+            }
+        }
+
+        struct Shared(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
             }
-            Ok(dest.join(Path::new(target).file_name().unwrap()))
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn stream_emits_lines_before_prefix_fills() {
+            let out = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let mut input = Trickle {
+                lines: vec!["软件\n", "第二行\n"],
+                out: out.clone(),
+                seen: Vec::new(),
+            };
+            let config = Config::load_from(&ConfigRoots::default(), &[]);
+            let converter = Converter::new(DefaultConfig::S2T).unwrap();
+            let opts = config.stream_options(false, Collect::None);
+            let writer = std::io::BufWriter::new(Shared(out.clone()));
+            converter.convert_stream(&mut input, writer, &opts).unwrap();
+            // 第一行在讀第二行之前就已寫出
+            assert_eq!(input.seen[0], 0);
+            assert!(input.seen[1] > 0, "{:?}", input.seen);
+            assert!(String::from_utf8_lossy(&out.borrow()).starts_with("軟"));
+        }
+
+        /// 以預設設定串流轉換，`format` 為 `None` 時依內容判斷
+        fn stream(text: &str, format: Option<Format>, lrc_metadata: bool) -> String {
+            let config = Config::load_from(&ConfigRoots::default(), &[]);
+            let mut opts = config.stream_options(false, Collect::None);
+            opts.format = format;
+            opts.lrc_metadata = lrc_metadata;
+            let mut out = Vec::new();
+            Converter::new(DefaultConfig::S2T)
+                .unwrap()
+                .convert_stream(text.as_bytes(), &mut out, &opts)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        #[test]
+        fn lrc_keeps_time_tags_and_numeric_ids() {
+            let c = Converter::new(DefaultConfig::S2T).unwrap();
+            assert_eq!(
+                c.convert_lrc_line("[00:01.00][00:30.5]软<00:01.50>软", false),
+                "[00:01.00][00:30.5]軟<00:01.50>軟"
+            );
+            assert_eq!(c.convert_lrc_line("[ti:软件]", false), "[ti:软件]");
+            assert_eq!(c.convert_lrc_line("[ti:软件]", true), "[ti:軟件]");
+            assert_eq!(c.convert_lrc_line("[offset:软]", true), "[offset:软]");
+            // 方括號內的一般文字不是時間標籤
+            assert_eq!(c.convert_lrc_line("[软]软", false), "[軟]軟");
+        }
+
+        #[test]
+        fn lrc_is_detected_when_streaming() {
+            let text = "[ti:软]\n[00:01.00]软件\n[00:02.00]软<00:02.50>件\n";
+            assert_eq!(
+                stream(text, None, false),
+                "[ti:软]\n[00:01.00]軟件\n[00:02.00]軟<00:02.50>件\n"
+            );
+            assert!(stream(text, None, true).starts_with("[ti:軟]\n"));
+        }
+
+        #[test]
+        fn plain_text_converts_whole_lines() {
+            let c = Converter::new(DefaultConfig::S2T).unwrap();
+            // 字幕模式保護 `{}` 與 `<>`，純文字不保護
+            assert_eq!(c.convert_line("{软}<软>软", ""), "{软}<软>軟");
+            assert_eq!(c.convert_text("{软}<软>软"), "{軟}<軟>軟");
+            // 看不出字幕結構的輸入以純文字轉換，數字行也照常處理
+            assert_eq!(stream("1\n{软}软\n", None, false), "1\n{軟}軟\n");
+        }
+
+        #[test]
+        fn markdown_fences_span_lines_when_streaming() {
+            let text = "软件 `软`\n```\n软\n```\n[软](软.md)\n";
+            assert_eq!(
+                stream(text, Some(Format::Markdown), false),
+                "軟件 `软`\n```\n软\n```\n[軟](软.md)\n"
+            );
+            // 未指定格式時 Markdown 不會被猜中，整行轉換
+            assert!(stream(text, None, false).contains("`軟`"));
+        }
+
+        /// 解析設定內容並逐一套用，回傳 (合併結果, 診斷)
+        fn apply_cfg(content: &str) -> (BTreeMap<String, ConfigEntry>, Vec<ConfigDiagnostic>) {
+            let source = ConfigSource::Project(PathBuf::from("cw.cfg"));
+            let mut entries = BTreeMap::new();
+            let (pairs, errs) = parse_cfg(content);
+            let mut diagnostics: Vec<_> = errs
+                .into_iter()
+                .map(|(line, kind)| ConfigDiagnostic {
+                    source: source.clone(),
+                    line,
+                    kind,
+                })
+                .collect();
+            for pair in pairs {
+                apply_entry(
+                    &mut entries,
+                    &mut diagnostics,
+                    &source,
+                    pair.line,
+                    &pair.key,
+                    pair.value,
+                );
+            }
+            diagnostics.sort_by_key(|d| d.line);
+            (entries, diagnostics)
+        }
+
+        fn values(content: &str) -> Vec<(String, String)> {
+            let (pairs, errs) = parse_cfg(content);
+            assert!(errs.is_empty(), "{:?}", errs);
+            pairs.into_iter().map(|p| (p.key, p.value)).collect()
+        }
+
+        #[test]
+        fn quoted_values_keep_hashes_and_escapes() {
+            let got = values(concat!(
+                "a = \"#5 # 不是註解\" # 註解\n",
+                "b = \"say \\\"hi\\\" \\\\ \\n\"\n",
+                "c = tag#1 # 註解\n",
+                "d = \"\" ; 註解\n",
+                "e = \"  前後空白  \"\n",
+            ));
+            let want = [
+                ("a", "#5 # 不是註解"),
+                ("b", "say \"hi\" \\ \\n"),
+                ("c", "tag#1"),
+                ("d", ""),
+                ("e", "  前後空白  "),
+            ];
+            let want: Vec<_> = want
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            assert_eq!(got, want);
+        }
+
+        #[test]
+        fn unterminated_quotes_are_errors() {
+            let (pairs, errs) = parse_cfg("a = \"abc\nb = \"abc\\\"\nc = \"x\" y\nd = ok\n");
+            assert_eq!(pairs.len(), 1);
+            assert_eq!(pairs[0].key, "d");
+            assert_eq!(errs[0], (1, ConfigErrorKind::UnterminatedQuote));
+            // 最後的 `\"` 是跳脫，引號仍未閉合
+            assert_eq!(errs[1], (2, ConfigErrorKind::UnterminatedQuote));
+            assert!(matches!(&errs[2], (3, ConfigErrorKind::Syntax(m)) if m.contains('y')));
+            assert_eq!(errs.len(), 3);
+        }
+
+        #[test]
+        fn diagnostics_carry_line_numbers() {
+            let (entries, diags) = apply_cfg(concat!(
+                "# 註解\n",
+                "\n",
+                "verbosity = 2\n",
+                "no_equals\n",
+                "[weird]\n",
+                "verbosity = 9\n",
+                "[profile.x]\n",
+                "conversion = s2hk\n",
+                "conversion = s2tw\n",
+                "no_such_key = 1\n",
+            ));
+            let got: Vec<_> = diags
+                .iter()
+                .map(|d| (d.line, d.is_error(), d.to_string()))
+                .collect();
+            assert_eq!(got.len(), 4, "{:?}", got);
+            assert_eq!(got[0].0, 4);
+            assert!(got[0].1 && got[0].2.starts_with("專案 cw.cfg:4: 語法錯誤"));
+            assert_eq!(
+                (got[1].0, got[1].1, got[1].2.as_str()),
+                (5, true, "專案 cw.cfg:5: 不支援的區段 [weird]")
+            );
+            // 重複鍵與未知鍵是警告，不算錯誤
+            assert_eq!(
+                (got[2].0, got[2].1, got[2].2.as_str()),
+                (
+                    9,
+                    false,
+                    "專案 cw.cfg:9: 重複的設定鍵 `conversion`，以最後一次為準"
+                )
+            );
+            assert_eq!(
+                (got[3].0, got[3].1, got[3].2.as_str()),
+                (
+                    10,
+                    false,
+                    "專案 cw.cfg:10: 未知的設定鍵 `no_such_key`，已忽略"
+                )
+            );
+            // 不支援的區段內的鍵值整段略過
+            assert_eq!(entries["verbosity"].value, "2");
+        }
+
+        #[test]
+        fn invalid_values_are_rejected() {
+            let (entries, diags) = apply_cfg(concat!(
+                "verbosity = -1\n",
+                "log_backup_count = many\n",
+                "phrase_mode = yes\n",
+                "smtp_port = 0\n",
+                "log_max_size = 10MB\n",
+                "auto_mail = true\n",
+            ));
+            let bad: Vec<_> = diags
+                .iter()
+                .map(|d| match &d.kind {
+                    ConfigErrorKind::InvalidValue { key, value, .. } => {
+                        (d.line, key.as_str(), value.as_str())
+                    }
+                    k => panic!("{:?}", k),
+                })
+                .collect();
+            assert_eq!(
+                bad,
+                vec![
+                    (1, "verbosity", "-1"),
+                    (2, "log_backup_count", "many"),
+                    (3, "phrase_mode", "yes"),
+                    (4, "smtp_port", "0"),
+                ]
+            );
+            assert!(diags.iter().all(ConfigDiagnostic::is_error));
+            // 無效值不套用，有效值照常套用
+            assert!(!entries.contains_key("verbosity"));
+            assert_eq!(entries["log_max_size"].value, "10MB");
+            assert_eq!(entries["auto_mail"].value, "true");
+        }
+
+        #[test]
+        fn only_discord_webhooks_are_accepted() {
+            let ok = "https://discord.com/api/webhooks/123/abc";
+            assert_eq!(validate_entry("discord_webhook", ok), Ok(()));
+            assert_eq!(validate_entry("discord_webhook", ""), Ok(()));
+            assert_eq!(
+                validate_entry(
+                    "discord_webhook",
+                    "https://ptb.discord.com/api/webhooks/1/t"
+                ),
+                Ok(())
+            );
+            for bad in [
+                "https://example.com/api/webhooks/123/abc",
+                "https://discord.com.evil.io/api/webhooks/123/abc",
+                "http://discord.com/api/webhooks/123/abc",
+                "https://discord.com/api/webhooks/123",
+                "https://hooks.slack.com/services/T/B/X",
+            ] {
+                assert_eq!(
+                    validate_entry("discord_webhook", bad),
+                    Err(ConfigErrorKind::InvalidWebhook(bad.to_string())),
+                    "{}",
+                    bad
+                );
+            }
+        }
+
+        /// 以固定選項串流轉換位元組 (不讀設定檔)，`set` 可調整選項
+        fn stream_bytes(
+            input: &[u8],
+            set: impl FnOnce(&mut StreamOptions),
+        ) -> (Vec<u8>, StreamSummary) {
+            let mut opts = StreamOptions {
+                fix_trailing_newline: false,
+                diagnose: false,
+                collect: Collect::None,
+                line_ending: LineEnding::Preserve,
+                bom: Bom::Preserve,
+                input_encoding: None,
+                output_encoding: encoding::OutputEncoding::Utf8,
+                repair_mojibake: false,
+                format: None,
+                output_format: None,
+                frame_rate: None,
+                lrc_metadata: false,
+            };
+            set(&mut opts);
+            let mut out = Vec::new();
+            let summary = Converter::new(DefaultConfig::S2T)
+                .unwrap()
+                .convert_stream(input, &mut out, &opts)
+                .unwrap();
+            (out, summary)
+        }
+
+        fn utf16le(text: &str) -> Vec<u8> {
+            let mut out = vec![0xFF, 0xFE];
+            out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            out
+        }
+
+        const CRLF_SRT: &str = "1\r\n00:00:01,000 --> 00:00:02,000\r\n软件\r\n\r\n";
+
+        #[test]
+        fn line_endings_and_bom_are_preserved_by_default() {
+            let want = "1\r\n00:00:01,000 --> 00:00:02,000\r\n軟件\r\n\r\n";
+            let (out, summary) = stream_bytes(CRLF_SRT.as_bytes(), |_| {});
+            assert_eq!(String::from_utf8(out).unwrap(), want);
+            assert!(summary.crlf && !summary.bom);
+            // UTF-8 BOM
+            let input = format!("\u{feff}{}", CRLF_SRT);
+            let (out, summary) = stream_bytes(input.as_bytes(), |_| {});
+            assert_eq!(String::from_utf8(out).unwrap(), format!("\u{feff}{}", want));
+            assert!(summary.bom);
+            // UTF-16LE 輸入帶 BOM：以輸出字集的 BOM 保留
+            let (out, summary) = stream_bytes(&utf16le(CRLF_SRT), |_| {});
+            assert_eq!(summary.encoding, encoding_rs::UTF_16LE);
+            assert_eq!(String::from_utf8(out).unwrap(), format!("\u{feff}{}", want));
+            let (out, _) = stream_bytes(&utf16le(CRLF_SRT), |o| {
+                o.output_encoding = encoding::OutputEncoding::Utf16Le
+            });
+            assert_eq!(out, utf16le(want));
+        }
+
+        #[test]
+        fn eol_and_bom_options_override_the_input() {
+            let lf = "1\n00:00:01,000 --> 00:00:02,000\n軟件\n\n";
+            let crlf = lf.replace('\n', "\r\n");
+            let (out, summary) =
+                stream_bytes(CRLF_SRT.as_bytes(), |o| o.line_ending = LineEnding::Lf);
+            assert_eq!(String::from_utf8(out).unwrap(), lf);
+            assert!(!summary.crlf);
+            let input = CRLF_SRT.replace("\r\n", "\n");
+            let (out, summary) =
+                stream_bytes(input.as_bytes(), |o| o.line_ending = LineEnding::CrLf);
+            assert_eq!(String::from_utf8(out).unwrap(), crlf);
+            assert!(summary.crlf);
+            let (out, summary) = stream_bytes(input.as_bytes(), |o| o.bom = Bom::Always);
+            assert_eq!(String::from_utf8(out).unwrap(), format!("\u{feff}{}", lf));
+            assert!(summary.bom);
+            let (out, summary) = stream_bytes(&utf16le(CRLF_SRT), |o| {
+                o.bom = Bom::Never;
+                o.line_ending = LineEnding::Lf;
+            });
+            assert_eq!(String::from_utf8(out).unwrap(), lf);
+            assert!(!summary.bom);
+        }
+
+        #[test]
+        fn trailing_newline_check_understands_crlf() {
+            assert!(!needs_trailing_newline("软件\r\n\r\n"));
+            assert!(!needs_trailing_newline("软件\n\r\n"));
+            assert!(needs_trailing_newline("软件\r\n"));
+            assert!(needs_trailing_newline("软件"));
+            assert!(needs_trailing_newline(""));
+            // 串流補上的空行沿用輸入的換行風格
+            let input = "1\r\n00:00:01,000 --> 00:00:02,000\r\n软件\r\n";
+            let (out, summary) = stream_bytes(input.as_bytes(), |o| o.fix_trailing_newline = true);
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                "1\r\n00:00:01,000 --> 00:00:02,000\r\n軟件\r\n\r\n"
+            );
+            assert!(summary.fixed_trailing_newline);
+            // 已有空行時不再補
+            let (out, summary) =
+                stream_bytes(CRLF_SRT.as_bytes(), |o| o.fix_trailing_newline = true);
+            assert!(String::from_utf8(out).unwrap().ends_with("軟件\r\n\r\n"));
+            assert!(!summary.fixed_trailing_newline);
+        }
+
+        /// 在 `dir` 下建立各層設定檔，回傳指向它們的 `ConfigRoots`
+        fn layered_roots(dir: &Path) -> ConfigRoots {
+            let layer = |rel: &str, body: &str| {
+                let path = dir.join(rel);
+                write(&path, body);
+                path
+            };
+            ConfigRoots {
+                system: Some(layer(
+                    "etc/cw.cfg",
+                    "verbosity = 2\nlog_file_prefix = sys\n",
+                )),
+                install: Some(layer("bin/cw.cfg", "verbosity = 3\nmention_id = inst\n")),
+                user: Some(layer(
+                    "xdg/cw/cw.cfg",
+                    "verbosity = 4\ndiscord_thread_name = user\n",
+                )),
+                project_start: Some(dir.join("proj").join("sub")),
+                project_ceiling: Some(dir.join("proj")),
+                env: vec![
+                    ("CW_VERBOSITY".to_string(), "6".to_string()),
+                    ("CW_SMTP_USER".to_string(), "env".to_string()),
+                ],
+            }
+        }
+
+        #[test]
+        fn layers_override_in_order() {
+            let dir = scratch("layers");
+            let mut roots = layered_roots(&dir);
+            fs::create_dir_all(dir.join("proj").join("sub")).unwrap();
+            fs::write(
+                dir.join("proj").join("cw.cfg"),
+                "verbosity = 5\nsmtp_host = proj\n",
+            )
+            .unwrap();
+            let cli = [("verbosity".to_string(), "7".to_string())];
+            let verbosity = |roots: &ConfigRoots, cli: &[(String, String)]| {
+                let c = Config::load_from(roots, cli);
+                assert!(c.diagnostics.is_empty(), "{:?}", c.diagnostics);
+                c.verbosity
+            };
+            // 由高到低逐層移除，每次都由剩下的最高層決定
+            assert_eq!(verbosity(&roots, &cli), 7);
+            assert_eq!(verbosity(&roots, &[]), 6);
+            roots.env.clear();
+            assert_eq!(verbosity(&roots, &[]), 5);
+            roots.project_start = None;
+            assert_eq!(verbosity(&roots, &[]), 4);
+            roots.user = None;
+            assert_eq!(verbosity(&roots, &[]), 3);
+            roots.install = None;
+            assert_eq!(verbosity(&roots, &[]), 2);
+            roots.system = None;
+            assert_eq!(verbosity(&roots, &[]), 1);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn entries_report_their_source() {
+            let dir = scratch("sources");
+            let roots = layered_roots(&dir);
+            fs::create_dir_all(dir.join("proj").join("sub")).unwrap();
+            let project = dir.join("proj").join("cw.cfg");
+            fs::write(&project, "smtp_host = proj\n").unwrap();
+            let cli = [("verbosity".to_string(), "7".to_string())];
+            let config = Config::load_from(&roots, &cli);
+            let source = |k: &str| config.entries[k].source.clone();
+            assert_eq!(source("verbosity"), ConfigSource::Cli);
+            assert_eq!(
+                source("smtp_user"),
+                ConfigSource::Env("CW_SMTP_USER".into())
+            );
+            assert_eq!(source("smtp_host"), ConfigSource::Project(project.clone()));
+            assert_eq!(
+                source("discord_thread_name"),
+                ConfigSource::User(dir.join("xdg/cw/cw.cfg"))
+            );
+            assert_eq!(
+                source("mention_id"),
+                ConfigSource::Install(dir.join("bin/cw.cfg"))
+            );
+            assert_eq!(
+                source("log_file_prefix"),
+                ConfigSource::System(dir.join("etc/cw.cfg"))
+            );
+            assert_eq!(source("conversion"), ConfigSource::Default);
+            // `cw config show` 顯示的來源文字
+            assert_eq!(source("smtp_user").to_string(), "環境變數 CW_SMTP_USER");
+            assert_eq!(
+                source("smtp_host").to_string(),
+                format!("專案 {}", project.display())
+            );
+            assert_eq!(
+                Config::discover(&roots)
+                    .into_iter()
+                    .map(|(_, s)| s)
+                    .collect::<Vec<_>>(),
+                vec![
+                    ConfigSource::System(dir.join("etc/cw.cfg")),
+                    ConfigSource::Install(dir.join("bin/cw.cfg")),
+                    ConfigSource::User(dir.join("xdg/cw/cw.cfg")),
+                    ConfigSource::Project(project),
+                ]
+            );
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn project_search_stops_at_the_ceiling() {
+            let dir = scratch("ceiling");
+            let deep = dir.join("a").join("b");
+            fs::create_dir_all(&deep).unwrap();
+            fs::write(dir.join("cw.cfg"), "verbosity = 3\n").unwrap();
+            let mut roots = ConfigRoots {
+                project_start: Some(deep.clone()),
+                ..Default::default()
+            };
+            assert_eq!(Config::load_from(&roots, &[]).verbosity, 3);
+            roots.project_ceiling = Some(dir.join("a"));
+            assert_eq!(Config::load_from(&roots, &[]).verbosity, 1);
+            assert!(Config::discover(&roots).is_empty());
+            // 終點目錄本身仍會檢查
+            fs::write(dir.join("a").join("cw.cfg"), "verbosity = 4\n").unwrap();
+            assert_eq!(Config::load_from(&roots, &[]).verbosity, 4);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        /// 只有一個專案設定檔的 `ConfigRoots`
+        fn project_roots(dir: &Path, content: &str) -> ConfigRoots {
+            fs::write(dir.join("cw.cfg"), content).unwrap();
+            ConfigRoots {
+                project_start: Some(dir.to_path_buf()),
+                project_ceiling: Some(dir.to_path_buf()),
+                ..Default::default()
+            }
+        }
+
+        fn with_profile(name: &str) -> Vec<(String, String)> {
+            vec![("profile".to_string(), name.to_string())]
+        }
+
+        const PROFILES: &str = "\
+verbosity = 2
+mention_id = base
+[profile.fansub]
+conversion = s2hk
+verbosity = 3
+[profile.hk]
+inherits = \"fansub\"
+verbosity = 4
+";
+
+        #[test]
+        fn profiles_inherit_and_override_the_base() {
+            let dir = scratch("profile_inherit");
+            let roots = project_roots(&dir, PROFILES);
+            let config = Config::load_from(&roots, &with_profile("hk"));
+            assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+            // 子 profile 覆蓋父 profile，父 profile 覆蓋全域設定
+            assert_eq!(config.verbosity, 4);
+            assert_eq!(config.conversion, "s2hk");
+            assert_eq!(config.mention_id, "base");
+            let project = ConfigSource::Project(dir.join("cw.cfg"));
+            assert_eq!(
+                config.entries["conversion"].source,
+                ConfigSource::Profile("fansub".into(), Box::new(project.clone()))
+            );
+            assert_eq!(
+                config.entries["verbosity"].source,
+                ConfigSource::Profile("hk".into(), Box::new(project.clone()))
+            );
+            assert_eq!(config.entries["mention_id"].source, project);
+            assert_eq!(config.profiles, ["fansub", "hk"]);
+            // 未選用 profile 時只有全域設定
+            let config = Config::load_from(&roots, &[]);
+            assert_eq!((config.verbosity, config.conversion.as_str()), (2, "auto"));
+            // 設定檔的 `profile` 鍵也能選用；環境變數與命令列仍高於 profile
+            let mut roots = project_roots(&dir, &format!("profile = fansub\n{}", PROFILES));
+            roots.env = vec![("CW_VERBOSITY".into(), "6".into())];
+            let config = Config::load_from(&roots, &[]);
+            assert_eq!((config.verbosity, config.conversion.as_str()), (6, "s2hk"));
+            let cli = [("conversion".to_string(), "s2tw".to_string())];
+            assert_eq!(Config::load_from(&roots, &cli).conversion, "s2tw");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn profile_cycles_are_reported() {
+            let dir = scratch("profile_cycle");
+            let roots = project_roots(
+                &dir,
+                "[profile.a]\ninherits = b\nverbosity = 3\n[profile.b]\ninherits = a\n",
+            );
+            let config = Config::load_from(&roots, &with_profile("a"));
+            let kinds: Vec<_> = config.diagnostics.iter().map(|d| &d.kind).collect();
+            assert_eq!(kinds, [&ConfigErrorKind::ProfileCycle("a → b → a".into())]);
+            assert!(config.has_errors());
+            // 循環時整條鏈都不套用
+            assert_eq!(config.verbosity, 1);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn unknown_profiles_are_errors() {
+            let dir = scratch("profile_unknown");
+            let roots = project_roots(&dir, PROFILES);
+            let config = Config::load_from(&roots, &with_profile("nope"));
+            assert_eq!(config.diagnostics.len(), 1);
+            let d = &config.diagnostics[0];
+            assert_eq!(d.kind, ConfigErrorKind::UnknownProfile("nope".into()));
+            assert_eq!(d.source, ConfigSource::Cli);
+            assert!(config.has_errors());
+            assert_eq!(config.verbosity, 2);
+            // 繼承不存在的父 profile：自身仍套用，並回報缺少的名稱
+            let roots = project_roots(&dir, "[profile.a]\ninherits = gone\nverbosity = 3\n");
+            let config = Config::load_from(&roots, &with_profile("a"));
+            let kinds: Vec<_> = config.diagnostics.iter().map(|d| &d.kind).collect();
+            assert_eq!(kinds, [&ConfigErrorKind::UnknownProfile("gone".into())]);
+            assert_eq!(config.verbosity, 3);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn logs_rotate_and_drop_the_oldest() {
+            let dir = scratch("rotate");
+            let log = dir.join("cw.log");
+            let backup = |n: u32| dir.join(format!("cw.log.{}", n));
+            fs::write(&log, vec![b'x'; 1024 * 1024]).unwrap();
+            fs::write(backup(1), "one").unwrap();
+            fs::write(backup(2), "two").unwrap();
+            rotate_log(&log, 1, 2).unwrap();
+            assert!(!log.exists());
+            assert_eq!(fs::metadata(backup(1)).unwrap().len(), 1024 * 1024);
+            assert_eq!(fs::read_to_string(backup(2)).unwrap(), "one");
+            assert!(!backup(3).exists());
+            // 保留數為 0 時直接刪除
+            fs::write(&log, vec![b'x'; 1024 * 1024]).unwrap();
+            rotate_log(&log, 1, 0).unwrap();
+            assert!(!log.exists());
+            assert_eq!(fs::read_to_string(backup(2)).unwrap(), "one");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn small_logs_are_not_rotated() {
+            let dir = scratch("no_rotate");
+            let log = dir.join("cw.log");
+            fs::write(&log, vec![b'x'; 1024 * 1024 - 1]).unwrap();
+            rotate_log(&log, 1, 2).unwrap();
+            assert!(log.exists());
+            // 0 表示不輪替
+            fs::write(&log, vec![b'x'; 2 * 1024 * 1024]).unwrap();
+            rotate_log(&log, 0, 2).unwrap();
+            assert!(log.exists());
+            assert!(!dir.join("cw.log.1").exists());
+            // 檔案不存在不是錯誤
+            rotate_log(&dir.join("none.log"), 1, 2).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn log_level_filters_records() {
+            let warn = [SubtitleIssue {
+                line: 1,
+                message: "x".to_string(),
+            }];
+            let cases = [
+                ("DEBUG", true, true, true),
+                ("INFO", true, true, true),
+                ("WARN", false, true, true),
+                ("error", false, false, true),
+                // 無法辨識的等級視為 INFO
+                ("loud", true, true, true),
+            ];
+            for (level, info, warning, error) in cases {
+                assert_eq!(
+                    should_log(level, &ResultStatus::Success, &[]),
+                    info,
+                    "{}",
+                    level
+                );
+                assert_eq!(
+                    should_log(level, &ResultStatus::Success, &warn),
+                    warning,
+                    "{}",
+                    level
+                );
+                assert_eq!(
+                    should_log(level, &ResultStatus::VerifWarning, &[]),
+                    warning,
+                    "{}",
+                    level
+                );
+                assert_eq!(
+                    should_log(level, &ResultStatus::ConvertError, &[]),
+                    error,
+                    "{}",
+                    level
+                );
+            }
+        }
+
+        #[test]
+        fn log_names_follow_the_date_format() {
+            let dir = scratch("log_name");
+            let config = |fmt: &str| {
+                let set = |k: &str, v: &str| (k.to_string(), v.to_string());
+                Config::load_from(
+                    &ConfigRoots::default(),
+                    &[
+                        set("log_directory", &dir.to_string_lossy()),
+                        set("log_file_prefix", "job"),
+                        set("log_file_date_format", fmt),
+                    ],
+                )
+            };
+            let today = Local::now().format("%Y%m").to_string();
+            assert_eq!(
+                config("%Y%m").log_path(),
+                dir.join(format!("job_{}.log", today))
+            );
+            assert_eq!(config("").log_path(), dir.join("job.log"));
+            // 無效格式不加日期
+            assert_eq!(config("%Q").log_path(), dir.join("job.log"));
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn missing_log_directory_is_created() {
+            let dir = scratch("log_dir");
+            let log = dir.join("a").join("b").join("cw.log");
+            create_log(
+                "in.srt",
+                "out.srt",
+                &log,
+                &ResultStatus::Success,
+                10,
+                5,
+                &[],
+            )
+            .unwrap();
+            let text = fs::read_to_string(&log).unwrap();
+            assert!(
+                text.contains("in.srt") && text.contains("out.srt"),
+                "{}",
+                text
+            );
+            fs::remove_dir_all(&dir).unwrap();
         }
     }
 }
@@ -1,143 +1,900 @@
+mod cli;
 mod mode_a_compare;
 mod mode_b_discord;
+mod mode_c_mail;
 mod ui_style;
 
-use cw::core;
-use cw::report_format::{FileReport, ResultStatus};
-use std::env;
+use chrono::Local;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use cli::{
+    Cli, Command, ConfigCommand, ConvertArgs, FilesArgs, GlobalArgs, InputArgs, NotifyOptions,
+    Outcome,
+};
+use cw::report_format::{AuditRecord, FileReport, ResultStatus, SubtitleIssue};
+use cw::Error;
+use cw::{core, encoding};
+use opencc_rust::DefaultConfig;
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::BufRead;
-use std::path::Path;
-use std::time::Instant;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-fn main() -> std::io::Result<()> {
-    let total_start = Instant::now();
-    let config = core::Config::load();
-    let args: Vec<String> = env::args().collect();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let global = cli.global;
+    let outcome = match cli.command {
+        Some(Command::Init) => io_outcome(core::Config::generate_default()),
+        Some(Command::Completions { shell }) => {
+            let shell = match shell {
+                cli::Shell::Bash => clap_complete::Shell::Bash,
+                cli::Shell::Zsh => clap_complete::Shell::Zsh,
+                cli::Shell::Fish => clap_complete::Shell::Fish,
+            };
+            clap_complete::generate(shell, &mut Cli::command(), "cw", &mut std::io::stdout());
+            Outcome::Success
+        }
+        Some(Command::Config(cmd)) => run_config(cmd, &global),
+        Some(Command::Check(f)) => run_check(&f, &global),
+        Some(Command::Fix(f)) => run_fix(&f, &global),
+        Some(Command::Compare(c)) => run_compare(&c.a, &c.b, &global),
+        Some(Command::Notify(n)) => run_notify(&n.files, &n.notify, &global),
+        Some(Command::Convert(c)) => run_convert(*c, &global),
+        None => {
+            let c = cli.convert;
+            if c.legacy_init {
+                io_outcome(core::Config::generate_default())
+            } else if c.legacy_compare {
+                if c.files.len() != 2 {
+                    Cli::command()
+                        .error(
+                            ErrorKind::WrongNumberOfValues,
+                            "-a 需要恰好兩個檔案：原檔與譯檔",
+                        )
+                        .exit();
+                }
+                run_compare(&c.files[0], &c.files[1], &global)
+            } else {
+                run_convert(c, &global)
+            }
+        }
+    };
+    outcome.strict(global.strict).into()
+}
 
-    // 系統初始化
-    if args.iter().any(|arg| arg == "--init") {
-        return core::Config::generate_default();
+fn io_outcome(r: cw::Result<()>) -> Outcome {
+    match r {
+        Ok(()) => Outcome::Success,
+        Err(e) => {
+            ui_style::print_check_err(&format!("失敗: {}", e));
+            Outcome::from_error(&e)
+        }
     }
+}
 
-    // 管道模式檢測
-    if !atty::is(atty::Stream::Stdin) {
-        let is_p = args.iter().any(|arg| arg == "-p") || config.phrase_mode;
-        run_stdin_mode(is_p);
-        return Ok(());
+/// 設定錯誤 (語法、型別、無效值) 為參數錯誤；未知或重複的鍵只是提醒，
+/// 以「警告」計，--strict 時轉為失敗。
+fn config_outcome(config: &core::Config) -> Outcome {
+    match config.check() {
+        Err(e) => Outcome::from_error(&e),
+        Ok(()) if config.diagnostics.is_empty() => Outcome::Success,
+        Ok(()) => Outcome::Warnings,
     }
+}
 
-    if args.len() < 2 {
-        ui_style::print_help();
-        return Ok(());
+fn run_compare(a: &str, b: &str, global: &GlobalArgs) -> Outcome {
+    let config = load_config(Some(a), global, None);
+    config_outcome(&config).merge(
+        match mode_a_compare::run_detailed_compare(
+            &compare_modes(&config),
+            a,
+            b,
+            config.format,
+            config.lrc_metadata,
+        ) {
+            Ok(true) => Outcome::Success,
+            Ok(false) => Outcome::Warnings,
+            Err(e) => {
+                ui_style::print_check_err(&format!("對比失敗: {}", e));
+                Outcome::from_error(&e)
+            }
+        },
+    )
+}
+
+/// 對比時接受的轉換：與 convert 相同的 `opencc_config()`；
+/// 未指定 conversion 時沿用雙模式感應，S2T 與 S2TWP 的譯檔皆算一致。
+fn compare_modes(config: &core::Config) -> Vec<DefaultConfig> {
+    let mode = config.opencc_config();
+    if !matches!(config.conversion.as_str(), "auto" | "") {
+        return vec![mode];
+    }
+    let other = if config.phrase_mode {
+        DefaultConfig::S2T
+    } else {
+        DefaultConfig::S2TWP
+    };
+    vec![mode, other]
+}
+
+/// 載入分層設定並把命令列旗標疊在最上層，設定問題即時輸出到 stderr。
+fn load_config(
+    anchor: Option<&str>,
+    global: &GlobalArgs,
+    notify: Option<&NotifyOptions>,
+) -> core::Config {
+    let mut overrides = load_overrides(global);
+    if let Some(n) = notify {
+        for (on, key) in [(n.discord, "auto_discord"), (n.mail, "auto_mail")] {
+            if on {
+                overrides.push((key.to_string(), "true".to_string()));
+            }
+        }
     }
+    let config = core::Config::load_layered(anchor.map(Path::new), &overrides);
+    ui_style::print_config_diagnostics(&config.diagnostics);
+    config
+}
 
-    let is_p = args.iter().any(|arg| arg == "-p") || config.phrase_mode;
-    let is_a = args.iter().any(|arg| arg == "-a");
-    let is_b = args.iter().any(|arg| arg == "-b") || config.auto_discord;
-    let is_d = args.iter().any(|arg| arg == "-d");
+fn run_config(cmd: ConfigCommand, global: &GlobalArgs) -> Outcome {
+    match cmd {
+        ConfigCommand::Show { file } => {
+            let config =
+                core::Config::load_layered(file.as_deref().map(Path::new), &load_overrides(global));
+            ui_style::print_config(&config);
+            ui_style::print_config_diagnostics(&config.diagnostics);
+            config_outcome(&config)
+        }
+        ConfigCommand::Check { file } => {
+            let anchor = file.as_deref().map(Path::new);
+            let config = core::Config::load_layered(anchor, &load_overrides(global));
+            ui_style::print_config_check(
+                &core::Config::discover(&core::ConfigRoots::from_env(anchor)),
+                &config,
+            );
+            config_outcome(&config)
+        }
+    }
+}
 
-    let task_url = args
-        .iter()
-        .position(|r| r == "--task")
-        .and_then(|i| args.get(i + 1))
-        .cloned();
-    let mut paths: Vec<String> = args
-        .into_iter()
-        .skip(1)
-        .filter(|a| !a.starts_with("-"))
-        .collect();
+/// 命令列層：旗標對應的設定鍵，以及可重複的 `--set 鍵=值`。
+fn load_overrides(global: &GlobalArgs) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    if global.phrase {
+        out.push(("phrase_mode".to_string(), "true".to_string()));
+    }
+    if let Some(name) = &global.profile {
+        out.push(("profile".to_string(), name.clone()));
+    }
+    if let Some(format) = &global.format {
+        out.push(("format".to_string(), format.clone()));
+    }
+    out.extend(global.set.iter().cloned());
+    out
+}
 
+/// 展開目錄與萬用字元，列出略過與找不到的項目。
+fn expand_inputs(
+    files: &[String],
+    input: &InputArgs,
+    config: &core::Config,
+    out_dir: Option<&Path>,
+) -> (Vec<core::InputFile>, Outcome) {
+    let exts = match &input.ext {
+        Some(list) => core::parse_extensions(list),
+        None => config.input_extensions.clone(),
+    };
+    let vars = template_vars(config);
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let filter = match core::InputFilter::new(input.recursive, exts, &input.include, &input.exclude)
+    {
+        Ok(f) => {
+            let mut f = f.skip_outputs(&config.output_template, &vars);
+            if let Some(to) = config.output_format {
+                f = f.skip_outputs(&core::retarget_template(&config.output_template, to), &vars);
+            }
+            match out_dir {
+                Some(d) => f.skip_dir(d),
+                None => f,
+            }
+        }
+        Err(e) => {
+            ui_style::print_check_err(&format!("無效的篩選規則: {}", e));
+            return (Vec::new(), Outcome::BadArgs);
+        }
+    };
+    let set = core::collect_inputs(files, &filter);
+    let mut outcome = Outcome::Success;
+    if config.verbosity > 0 {
+        for (p, why) in &set.skipped {
+            println!(
+                "  {} 略過 {} ({})",
+                ui_style::status_info(),
+                p.display(),
+                why
+            );
+        }
+    }
+    for m in &set.missing {
+        ui_style::print_check_err(&format!("找不到: {}", m));
+        outcome = Outcome::IoError;
+    }
+    (set.files, outcome)
+}
+
+/// 輸出檔名模板中 {name} {stem} {ext} 以外的欄位
+fn template_vars(config: &core::Config) -> [(&'static str, String); 2] {
+    [
+        ("profile", config.profile.clone()),
+        ("conversion", config.conversion_label().to_lowercase()),
+    ]
+}
+
+/// 決定每個輸入的輸出路徑；會覆寫輸入檔 (未加 -d) 或與前一個輸出相撞者列為錯誤並略過。
+fn plan_outputs(
+    inputs: Vec<core::InputFile>,
+    config: &core::Config,
+    overwrite: bool,
+) -> (Vec<(String, String)>, Outcome) {
+    let out_dir = Some(Path::new(&config.output_dir)).filter(|d| !d.as_os_str().is_empty());
+    let vars = template_vars(config);
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let mut jobs: Vec<(String, String)> = Vec::new();
+    let mut outcome = Outcome::Success;
+    for input in inputs {
+        let src = input.path.to_string_lossy().to_string();
+        let out = if overwrite {
+            src.clone()
+        } else {
+            core::output_path(
+                &input,
+                &config.output_template,
+                config.output_format,
+                out_dir,
+                &vars,
+            )
+            .to_string_lossy()
+            .to_string()
+        };
+        let conflict = if !overwrite && same_file(&src, &out) {
+            Some("輸出會覆寫輸入檔，請改用 -d 或調整 output_template".to_string())
+        } else {
+            jobs.iter()
+                .find(|(_, o)| same_file(o, &out))
+                .map(|(s, _)| format!("輸出 {} 與 {} 的輸出相同", out, s))
+        };
+        match conflict {
+            Some(msg) => {
+                ui_style::print_check_err(&format!("略過 {}: {}", src, msg));
+                outcome = Outcome::BadArgs;
+            }
+            None => jobs.push((src, out)),
+        }
+    }
+    (jobs, outcome)
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    let norm = |p: &str| fs::canonicalize(p).unwrap_or_else(|_| Path::new(p).to_path_buf());
+    a == b || norm(a) == norm(b)
+}
+
+fn run_convert(c: ConvertArgs, global: &GlobalArgs) -> Outcome {
+    let total_start = Instant::now();
+    let mut global = global.clone();
+    if let Some(d) = &c.output_dir {
+        global.set.push(("output_dir".to_string(), d.clone()));
+    }
+    if let Some(t) = &c.name {
+        global.set.push(("output_template".to_string(), t.clone()));
+    }
+    if let Some(e) = &c.eol {
+        global.set.push(("line_ending".to_string(), e.clone()));
+    }
+    if let Some(b) = &c.bom {
+        global.set.push(("bom".to_string(), b.clone()));
+    }
+    if let Some(e) = &c.input_encoding {
+        global.set.push(("input_encoding".to_string(), e.clone()));
+    }
+    if let Some(e) = &c.output_encoding {
+        global.set.push(("output_encoding".to_string(), e.clone()));
+    }
+    if c.repair_mojibake {
+        global
+            .set
+            .push(("repair_mojibake".to_string(), "true".to_string()));
+    }
+    if c.lrc_metadata {
+        global
+            .set
+            .push(("lrc_metadata".to_string(), "true".to_string()));
+    }
+    if let Some(to) = &c.to {
+        global.set.push(("output_format".to_string(), to.clone()));
+    }
+    if let Some(fps) = &c.fps {
+        global.set.push(("frame_rate".to_string(), fps.clone()));
+    }
+    let config = load_config(
+        c.files.first().map(String::as_str),
+        &global,
+        Some(&c.notify),
+    );
+    let mut outcome = config_outcome(&config);
+
+    // 管道模式：未給檔案且 stdin 非終端
+    if c.files.is_empty() && c.task.is_none() {
+        if atty::is(atty::Stream::Stdin) {
+            Cli::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "請指定要轉換的檔案，或以管道輸入文字",
+                )
+                .exit();
+        }
+        return outcome.merge(io_outcome(run_stdin_mode(&config)));
+    }
+
+    let out_dir = Some(Path::new(&config.output_dir)).filter(|d| !d.as_os_str().is_empty());
+    let (mut inputs, found) = expand_inputs(&c.files, &c.input, &config, out_dir);
+    outcome = outcome.merge(found);
     // 直接使用 core 內部的下載器
-    if let Some(ref url) = task_url {
+    if let Some(ref url) = c.task {
         let dl_dir = Path::new(&config.log_directory).join("cw_tasks");
         let _ = fs::create_dir_all(&dl_dir);
-        if let Ok(target) = core::MegaDownloader::scout_target(url) {
-            if let Ok(local) = core::MegaDownloader::fetch_file(url, &target, &dl_dir) {
-                paths.push(local.to_string_lossy().to_string());
+        match core::MegaDownloader::scout_target(url)
+            .and_then(|target| core::MegaDownloader::fetch_file(url, &target, &dl_dir))
+        {
+            Ok(local) => inputs.push(core::InputFile {
+                rel: local.file_name().map(PathBuf::from).unwrap_or_default(),
+                path: local,
+            }),
+            Err(e) => {
+                ui_style::print_check_err(&format!("下載失敗: {}", e));
+                outcome = outcome.merge(Outcome::IoError);
             }
         }
     }
 
-    if is_a {
-        if paths.len() >= 2 {
-            mode_a_compare::run_detailed_compare(is_p, &paths[0], &paths[1]);
+    let (jobs, planned) = plan_outputs(inputs, &config, c.overwrite);
+    outcome = outcome.merge(planned);
+    println!(
+        "\n\x1b[1;36m🚀 CW 1.9.3 | 模式: {} | 日誌等級: {}\x1b[0m",
+        config.conversion_label(),
+        config.log_level
+    );
+    let mut reports = Vec::new();
+    let converter = match core::Converter::new(config.opencc_config()) {
+        Ok(conv) => conv,
+        Err(e) => {
+            ui_style::print_check_err(&e.to_string());
+            return outcome.merge(Outcome::ConvertError);
         }
-    } else {
-        println!(
-            "\n\x1b[1;36m🚀 CW 1.9.3 | 模式: {} | 日誌等級: {}\x1b[0m",
-            if is_p { "S2TWP" } else { "S2T" },
-            config.log_level
-        );
-        let mut reports = Vec::new();
-        for (idx, path_str) in paths.iter().enumerate() {
-            let file_start = Instant::now();
-            ui_style::print_file_header(idx + 1, paths.len(), path_str);
-            let fix = core::needs_trailing_newline_fix(path_str);
-            let issues = core::diagnose_file(path_str, config.translate_error);
-
-            match core::run_safe_translate(is_p, path_str, &format!("{}.tmp", path_str), fix) {
-                Ok(pairs) => {
-                    ui_style::print_translated_preview(&pairs, config.full_preview, &issues);
-                    let out_name = if is_d {
-                        fs::rename(format!("{}.tmp", path_str), path_str)?;
-                        path_str.to_string()
-                    } else {
-                        let n = format!("{}.txt", path_str);
-                        let _ = fs::rename(format!("{}.tmp", path_str), &n);
-                        n
-                    };
-                    let log_p = Path::new(&config.log_directory)
-                        .join(format!("{}.log", config.log_file_prefix));
-                    let _ = core::create_log(
-                        path_str,
-                        &out_name,
-                        &log_p,
-                        &ResultStatus::Success,
-                        config.log_max_size_mb,
-                        config.log_backup_count,
-                        &issues,
-                    );
-                    reports.push(FileReport {
-                        input_name: path_str.clone(),
-                        output_name: out_name,
-                        temp_log_path: log_p,
-                        status: ResultStatus::Success,
-                        issues,
-                        translated_pairs: pairs,
-                        duration: file_start.elapsed(),
-                    });
-                    ui_style::print_check_ok("處理完成");
+    };
+    let workers = match c.jobs.unwrap_or(config.jobs) {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(jobs.len().max(1));
+    // 工作執行緒只做轉換；輸出、日誌與稽核一律由主執行緒依原順序處理，
+    // 因此結果與逐檔處理完全相同，畫面也不會交錯。
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let (next, jobs, converter, config) = (&next, &jobs, &converter, &config);
+            scope.spawn(move || loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                let Some((src, out)) = jobs.get(idx) else {
+                    break;
+                };
+                if tx
+                    .send((idx, convert_one(converter, config, src, out)))
+                    .is_err()
+                {
+                    break;
                 }
-                Err(e) => ui_style::print_check_err(&format!("失敗: {}", e)),
-            }
-        }
-        ui_style::print_summary(&reports, total_start.elapsed());
-        if is_b && !config.discord_webhook.is_empty() {
-            let _ = mode_b_discord::execute(
-                &config.discord_webhook,
-                None,
-                &config.mention_id,
-                config.discord_interval,
-                config.show_stats,
-                config.discord_show_errors,
-                &reports,
-            );
+            });
+        }
+        drop(tx);
+        let mut pending = BTreeMap::new();
+        let mut shown = 0;
+        for (idx, done) in rx {
+            pending.insert(idx, done);
+            while let Some(done) = pending.remove(&shown) {
+                let (src, out) = &jobs[shown];
+                ui_style::print_file_header(shown + 1, jobs.len(), src);
+                outcome = outcome.merge(report_one(&config, src, out, done, &mut reports));
+                shown += 1;
+            }
+        }
+    });
+    ui_style::print_summary(&reports, total_start.elapsed());
+    outcome.merge(send_notifications(
+        &config,
+        &c.notify,
+        &reports,
+        total_start.elapsed(),
+        false,
+    ))
+}
+
+/// 單檔轉換結果，由工作執行緒產生、主執行緒輸出。
+struct Converted {
+    result: cw::Result<core::StreamSummary>,
+    duration: Duration,
+}
+
+fn convert_one(
+    converter: &core::Converter,
+    config: &core::Config,
+    src: &str,
+    out: &str,
+) -> Converted {
+    let start = Instant::now();
+    // 診斷、檔尾空行與稽核雜湊都在串流轉換時逐塊完成，不另外整份讀入
+    // 影子檔放在輸出旁，確保 rename 不跨檔案系統
+    let tmp = format!("{}.tmp", out);
+    let result = Path::new(out)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|e| Error::io_at(out, e))
+        .and_then(|_| {
+            let mut opts =
+                config.stream_options(true, core::Collect::Head(ui_style::PREVIEW_LINES));
+            opts.diagnose = true;
+            converter.convert_file(src, &tmp, &opts)
+        })
+        .and_then(|summary| {
+            fs::rename(&tmp, out)
+                .map(|_| summary)
+                .map_err(|e| Error::io_at(out, e))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Converted {
+        result,
+        duration: start.elapsed(),
+    }
+}
+
+fn report_one(
+    config: &core::Config,
+    src: &str,
+    out: &str,
+    done: Converted,
+    reports: &mut Vec<FileReport>,
+) -> Outcome {
+    let Converted { result, duration } = done;
+    let mut fixes = Vec::new();
+    // 轉換失敗時原檔未被覆寫，稽核雜湊事後計算即可
+    let audit_input = result
+        .as_ref()
+        .ok()
+        .map(|summary| (summary.input_sha256.clone(), Some(summary.encoding)));
+    let (outcome, report) = match result {
+        Ok(summary) => {
+            let mut issues = summary.issues;
+            if summary.fixed_trailing_newline {
+                fixes.push("trailing_newline".to_string());
+            }
+            for (line, chains) in &summary.repaired {
+                let chain = chains
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" → ");
+                println!(
+                    "  {} L{:03} 已修復亂碼 ({})",
+                    ui_style::status_fixd(),
+                    line,
+                    chain
+                );
+                fixes.push(format!("mojibake:L{}", line));
+            }
+            if summary.confidence == encoding::Confidence::Low {
+                println!(
+                    "  {} 編碼偵測可信度低 (判定為 {})，可用 --input-encoding 指定",
+                    ui_style::status_info(),
+                    summary.encoding.name()
+                );
+            }
+            let target = config.output_encoding.name();
+            let mut lost = Vec::new();
+            for (line, ch) in &summary.unmappable {
+                lost.push(SubtitleIssue {
+                    line: *line,
+                    message: format!(
+                        "字元「{}」(U+{:04X}) 無法以 {} 表示，已以 {} 取代",
+                        ch,
+                        *ch as u32,
+                        target,
+                        encoding::REPLACEMENT
+                    ),
+                });
+            }
+            if summary.unmappable_count > summary.unmappable.len() {
+                lost.push(SubtitleIssue {
+                    line: 0,
+                    message: format!(
+                        "另有 {} 個字元無法以 {} 表示",
+                        summary.unmappable_count - summary.unmappable.len(),
+                        target
+                    ),
+                });
+            }
+            issues.extend(lost.iter().cloned());
+            ui_style::print_translated_preview(&summary.pairs, config.full_preview, &issues);
+            ui_style::print_footnotes(&lost);
+            write_log(config, src, out, &ResultStatus::Success, &issues);
+            let outcome = if issues.is_empty() {
+                Outcome::Success
+            } else {
+                Outcome::Warnings
+            };
+            let report = FileReport {
+                input_name: src.to_string(),
+                output_name: out.to_string(),
+                temp_log_path: config.log_path(),
+                status: ResultStatus::Success,
+                issues,
+                translated_pairs: summary.pairs,
+                changed_lines: summary.changed,
+                duration,
+            };
+            (outcome, report)
+        }
+        Err(e) => {
+            ui_style::print_check_err(&format!("失敗: {}", e));
+            let issues = vec![SubtitleIssue {
+                line: 0,
+                message: format!("轉換失敗：{}", e),
+            }];
+            write_log(config, src, "", &ResultStatus::ConvertError, &issues);
+            let report = FileReport {
+                input_name: src.to_string(),
+                output_name: String::new(),
+                temp_log_path: config.log_path(),
+                status: ResultStatus::ConvertError,
+                issues,
+                translated_pairs: Vec::new(),
+                changed_lines: 0,
+                duration,
+            };
+            (Outcome::from_error(&e), report)
+        }
+    };
+    if report.status == ResultStatus::ConvertError {
+        fixes.clear();
+    }
+    if !config.audit_log.is_empty() {
+        let (input_sha256, used) =
+            audit_input.unwrap_or_else(|| (core::sha256_file(src).unwrap_or_default(), None));
+        write_audit(config, input_sha256, used, &report, fixes);
+    }
+    if report.status != ResultStatus::ConvertError {
+        ui_style::print_check_ok("處理完成");
+    }
+    reports.push(report);
+    outcome
+}
+
+fn run_check(args: &FilesArgs, global: &GlobalArgs) -> Outcome {
+    let config = load_config(args.files.first().map(String::as_str), global, None);
+    let (files, found) = expand_inputs(&args.files, &args.input, &config, None);
+    let files: Vec<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    let mut outcome = config_outcome(&config).merge(found);
+    for (idx, f) in files.iter().enumerate() {
+        ui_style::print_file_header(idx + 1, files.len(), f);
+        let issues = match encoding::read_file(f, config.input_encoding) {
+            Ok(decoded) => {
+                let format = config.format_for(f, &decoded.text);
+                println!("  {} 格式：{}", ui_style::status_info(), format);
+                core::diagnose_as(&decoded, format)
+            }
+            Err(e) => {
+                ui_style::print_check_err(&format!("無法讀取: {}", e));
+                outcome = outcome.merge(Outcome::from_error(&e));
+                continue;
+            }
+        };
+        if issues.is_empty() {
+            ui_style::print_check_ok("未發現問題");
+        } else {
+            ui_style::print_footnotes(&issues);
+            outcome = outcome.merge(Outcome::Warnings);
         }
     }
+    outcome
+}
+
+fn run_fix(args: &FilesArgs, global: &GlobalArgs) -> Outcome {
+    let config = load_config(args.files.first().map(String::as_str), global, None);
+    let (files, found) = expand_inputs(&args.files, &args.input, &config, None);
+    let files: Vec<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    files
+        .iter()
+        .enumerate()
+        .map(|(idx, f)| io_outcome(fix_file(idx, files.len(), f, &config)))
+        .fold(config_outcome(&config).merge(found), Outcome::merge)
+}
+
+fn fix_file(idx: usize, total: usize, f: &str, config: &core::Config) -> cw::Result<()> {
+    ui_style::print_file_header(idx + 1, total, f);
+    let at = |e| Error::io_at(f, e);
+    let mut data = fs::read(f).map_err(at)?;
+    let decoded = encoding::decode(&data, config.input_encoding);
+    let format = config.format_for(f, &decoded.text);
+    if !format.is_srt() {
+        ui_style::print_check_ok(&format!("{} 格式無檔尾空行規範，略過", format));
+        return Ok(());
+    }
+    if !core::needs_trailing_newline(&decoded.text) {
+        ui_style::print_check_ok("檔尾空行正常");
+        return Ok(());
+    }
+    let eol = if decoded.text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut tail = String::new();
+    if !decoded.text.ends_with('\n') {
+        tail.push_str(eol);
+    }
+    tail.push_str(eol);
+    // 依原檔編碼補上換行，UTF-16 檔不能直接附加 ASCII 位元組
+    match decoded.detection.encoding.name() {
+        "UTF-16LE" => tail
+            .encode_utf16()
+            .for_each(|u| data.extend_from_slice(&u.to_le_bytes())),
+        "UTF-16BE" => tail
+            .encode_utf16()
+            .for_each(|u| data.extend_from_slice(&u.to_be_bytes())),
+        _ => data.extend_from_slice(tail.as_bytes()),
+    }
+    // 影子檔案：寫完才取代原檔
+    let tmp = format!("{}.tmp", f);
+    fs::write(&tmp, &data).map_err(at)?;
+    fs::rename(&tmp, f).map_err(at)?;
+    println!("  {} 已補齊檔尾空行", ui_style::status_fixd());
     Ok(())
 }
 
-fn run_stdin_mode(is_phrase: bool) {
-    let config = if is_phrase {
-        opencc_rust::DefaultConfig::S2TWP
+fn run_notify(files: &[String], opts: &NotifyOptions, global: &GlobalArgs) -> Outcome {
+    let start = Instant::now();
+    let config = load_config(files.first().map(String::as_str), global, Some(opts));
+    let reports: Vec<FileReport> = files
+        .iter()
+        .map(|f| FileReport {
+            input_name: f.clone(),
+            output_name: f.clone(),
+            temp_log_path: config.log_path(),
+            status: ResultStatus::Success,
+            issues: core::diagnose_file_with(f, config.input_encoding, config.format),
+            translated_pairs: Vec::new(),
+            changed_lines: 0,
+            duration: Duration::ZERO,
+        })
+        .collect();
+    // 未指定 -b / -m 時，發送到所有已設定的管道
+    let all = !opts.discord && !opts.mail;
+    config_outcome(&config).merge(send_notifications(
+        &config,
+        opts,
+        &reports,
+        start.elapsed(),
+        all,
+    ))
+}
+
+fn send_notifications(
+    config: &core::Config,
+    opts: &NotifyOptions,
+    reports: &[FileReport],
+    total: Duration,
+    all: bool,
+) -> Outcome {
+    let mut outcome = Outcome::Success;
+    if (all || config.auto_discord) && !config.discord_webhook.is_empty() {
+        let thread_id = opts
+            .thread
+            .clone()
+            .unwrap_or(config.discord_thread_id.clone());
+        let thread_name = opts
+            .thread_name
+            .clone()
+            .unwrap_or(config.discord_thread_name.clone());
+        let target =
+            mode_b_discord::Target::new(&config.discord_webhook, &thread_id, &thread_name, reports);
+        let template = config_template(opts, config);
+        let msg = mode_b_discord::MessageOptions {
+            intro: opts.intro.as_deref(),
+            template: template.as_deref(),
+            mention_id: &config.mention_id,
+            show_stats: config.show_stats,
+            show_errors: config.discord_show_errors,
+            duration: total,
+        };
+        match mode_b_discord::execute(&target, &msg, config.discord_interval, reports) {
+            Ok(()) => ui_style::print_check_ok("Discord 已發送"),
+            Err(e) => {
+                ui_style::print_check_err(&format!("Discord 失敗: {}", e));
+                outcome = Outcome::IoError;
+            }
+        }
+    }
+    if (all || config.auto_mail) && !config.smtp_host.is_empty() {
+        match mode_c_mail::execute(config, reports, total) {
+            Ok(()) => ui_style::print_check_ok("郵件已寄出"),
+            Err(e) => {
+                ui_style::print_check_err(&format!("郵件失敗: {}", e));
+                outcome = Outcome::IoError;
+            }
+        }
+    }
+    outcome
+}
+
+/// CLI 的 --template 優先於 cw.cfg 的 discord_template；讀不到時退回內建排版。
+fn config_template(opts: &NotifyOptions, config: &core::Config) -> Option<String> {
+    let path = opts
+        .template
+        .clone()
+        .unwrap_or(config.discord_template.clone());
+    if path.is_empty() {
+        return None;
+    }
+    match fs::read_to_string(&path) {
+        Ok(t) => Some(t),
+        Err(e) => {
+            ui_style::print_check_err(&format!("讀取模板失敗 {}: {}", path, e));
+            None
+        }
+    }
+}
+
+fn write_log(
+    config: &core::Config,
+    input: &str,
+    output: &str,
+    status: &ResultStatus,
+    issues: &[SubtitleIssue],
+) {
+    if !core::should_log(&config.log_level, status, issues) {
+        return;
+    }
+    let log_p = config.log_path();
+    if let Err(e) = core::create_log(
+        input,
+        output,
+        &log_p,
+        status,
+        config.log_max_size_mb,
+        config.log_backup_count,
+        issues,
+    ) {
+        ui_style::print_check_err(&format!("日誌寫入失敗: {}", e));
+    }
+}
+
+/// `used` 為轉換實際採用的輸入編碼；轉換失敗時改記指定編碼或偵測結果。
+fn write_audit(
+    config: &core::Config,
+    input_sha256: String,
+    used: Option<&'static encoding_rs::Encoding>,
+    report: &FileReport,
+    fixes: Vec<String>,
+) {
+    // 轉換失敗時沒有實際使用的編碼，改記指定或偵測所得的編碼
+    let encoding = used.or(config.input_encoding).unwrap_or_else(|| {
+        core::detect_file_encoding(&report.input_name).unwrap_or(encoding_rs::UTF_8)
+    });
+    let output_sha256 = if report.output_name.is_empty() {
+        None
     } else {
-        opencc_rust::DefaultConfig::S2T
+        fs::read(&report.output_name)
+            .ok()
+            .map(|d| core::sha256_hex(&d))
+    };
+    let record = AuditRecord {
+        timestamp: Local::now().to_rfc3339(),
+        input: report.input_name.clone(),
+        output: report.output_name.clone(),
+        input_sha256,
+        output_sha256,
+        encoding: encoding.name().to_string(),
+        conversion: config.conversion_label(),
+        profile: Some(config.profile.clone()).filter(|p| !p.is_empty()),
+        glossary: format!(
+            "opencc:{}@cw-{}",
+            config.opencc_config().get_file_name(),
+            env!("CARGO_PKG_VERSION")
+        ),
+        status: report.status.clone(),
+        issues: report.issues.clone(),
+        fixes,
+        duration_ms: report.duration.as_millis(),
     };
-    let conv = opencc_rust::OpenCC::new(config).unwrap();
-    let guard = core::RawGuard::new();
-    let stdin = std::io::stdin();
-    for line in stdin.lock().lines().map_while(Result::ok) {
-        println!("{}", core::translate_single_line(&conv, &guard, &line, ""));
+    if let Err(e) = core::append_audit(Path::new(&config.audit_log), &record) {
+        ui_style::print_check_err(&format!("稽核日誌寫入失敗: {}", e));
+    }
+}
+
+fn run_stdin_mode(config: &core::Config) -> cw::Result<()> {
+    let converter = core::Converter::new(config.opencc_config())?;
+    let opts = config.stream_options(false, core::Collect::None);
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    converter.convert_stream(std::io::stdin().lock(), &mut out, &opts)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每個測試獨立的暫存目錄，供本執行檔內各模組的測試共用
+    pub(crate) fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cw_main_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(set: &[(&str, &str)]) -> core::Config {
+        let set: Vec<(String, String)> = set
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        core::Config::load_from(&core::ConfigRoots::default(), &set)
+    }
+
+    fn names(modes: &[DefaultConfig]) -> Vec<&'static str> {
+        modes.iter().map(|m| m.get_file_name()).collect()
+    }
+
+    #[test]
+    fn config_errors_are_bad_args() {
+        let c = config(&[("verbosity", "abc")]);
+        assert_eq!(config_outcome(&c), Outcome::BadArgs);
+        assert_eq!(config_outcome(&c).strict(true), Outcome::BadArgs);
+    }
+
+    #[test]
+    fn unknown_config_keys_are_warnings() {
+        let c = config(&[("no_such_key", "1")]);
+        assert!(!c.diagnostics.is_empty());
+        assert_eq!(config_outcome(&c), Outcome::Warnings);
+        assert_eq!(config_outcome(&c).strict(true), Outcome::ConvertError);
+        assert_eq!(config_outcome(&config(&[])), Outcome::Success);
+    }
+
+    #[test]
+    fn compare_uses_configured_conversion() {
+        let c = config(&[("conversion", "s2hk")]);
+        assert_eq!(names(&compare_modes(&c)), names(&[c.opencc_config()]));
+        assert_eq!(names(&compare_modes(&c)), ["s2hk.json"]);
+    }
+
+    #[test]
+    fn compare_auto_accepts_both_legacy_modes() {
+        let c = config(&[("conversion", "auto"), ("phrase_mode", "true")]);
+        assert_eq!(names(&compare_modes(&c)), ["s2twp.json", "s2t.json"]);
     }
 }
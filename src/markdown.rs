@@ -0,0 +1,179 @@
+// ==========================================
+// CW Markdown Module
+// ==========================================
+//
+// 翻譯筆記、腳本與發布說明多半是 Markdown：逐行轉換，但程式碼、網址與連結目標不可動。
+// 圍欄程式碼區塊跨行，開頭的圍欄由呼叫端保存 (與 ASS 區段相同，逐行傳入)。
+
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// 行內需保留的片段：HTML 標籤與自動連結、`](目標)`、`][參考]`、網址
+static PROTECT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"</?[A-Za-z][^<>]*>|\]\(\s*<?[^)\s>]*>?|\]\[[^\]]*\]|(?:https?|ftp)://[^\s<>，。、；：！？（）「」『』【】]+",
+    )
+    .expect("內建樣式")
+});
+/// 參考定義 `[標籤]: 網址`；之後的標題照常轉換
+static REF_DEF_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^ {0,3}\[[^\]]+\]:\s*(?:<[^>]*>|\S+)").expect("內建樣式"));
+
+/// 轉換一行 Markdown；`fence` 為目前所在的圍欄程式碼區塊開頭 (不在區塊內為空字串)。
+/// 圍欄區塊整行保留；其餘行保留行內程式碼、網址、連結目標、參考定義與 HTML 標籤。
+pub fn convert_line(
+    line: &str,
+    fence: &mut String,
+    mut convert: impl FnMut(&str) -> String,
+) -> String {
+    let t = line.trim_start_matches(' ');
+    let indent = line.len() - t.len();
+    let run = match t.as_bytes().first() {
+        Some(&c @ (b'`' | b'~')) => t.bytes().take_while(|&b| b == c).count(),
+        _ => 0,
+    };
+    if !fence.is_empty() {
+        // 結尾圍欄：同一字元、不短於開頭、其後只有空白
+        if indent < 4
+            && run >= fence.len()
+            && t.starts_with(&fence[..1])
+            && t[run..].trim().is_empty()
+        {
+            fence.clear();
+        }
+        return line.to_string();
+    }
+    if indent < 4 && run >= 3 && !(t.starts_with('`') && t[run..].contains('`')) {
+        *fence = t[..run].to_string();
+        return line.to_string();
+    }
+
+    let mut keep = code_spans(line);
+    if let Some(m) = REF_DEF_RE.find(line) {
+        keep.push(m.range());
+    }
+    keep.extend(PROTECT_RE.find_iter(line).map(|m| m.range()));
+    keep.sort_by_key(|r| r.start);
+
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+    for r in keep {
+        if r.start < last {
+            // 與前一段重疊：合併
+            if r.end > last {
+                out.push_str(&line[last..r.end]);
+                last = r.end;
+            }
+            continue;
+        }
+        out.push_str(&convert(&line[last..r.start]));
+        out.push_str(&line[r.clone()]);
+        last = r.end;
+    }
+    out.push_str(&convert(&line[last..]));
+    out
+}
+
+/// 行內程式碼：以 n 個反引號開頭，到下一個同長度的反引號串結束；找不到結尾時不是程式碼。
+fn code_spans(line: &str) -> Vec<Range<usize>> {
+    let bytes = line.as_bytes();
+    let run_at = |i: usize| bytes[i..].iter().take_while(|&&b| b == b'`').count();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let open = run_at(i);
+        let mut j = i + open;
+        let mut close = None;
+        while j < bytes.len() {
+            if bytes[j] == b'`' {
+                let n = run_at(j);
+                if n == open {
+                    close = Some(j + n);
+                    break;
+                }
+                j += n;
+            } else {
+                j += 1;
+            }
+        }
+        match close {
+            Some(end) => {
+                spans.push(i..end);
+                i = end;
+            }
+            None => i += open,
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(line: &str, fence: &mut String) -> String {
+        convert_line(line, fence, |s| s.replace('软', "軟"))
+    }
+
+    fn one(line: &str) -> String {
+        convert(line, &mut String::new())
+    }
+
+    #[test]
+    fn fenced_blocks_are_kept_until_matching_fence() {
+        let mut fence = String::new();
+        assert_eq!(convert("````rust", &mut fence), "````rust");
+        assert_eq!(fence, "````");
+        assert_eq!(convert("let 软 = 1;", &mut fence), "let 软 = 1;");
+        // 較短或不同字元的圍欄不會結束區塊
+        assert_eq!(convert("```", &mut fence), "```");
+        assert_eq!(convert("~~~~", &mut fence), "~~~~");
+        assert_eq!(fence, "````");
+        assert_eq!(convert("`````  ", &mut fence), "`````  ");
+        assert!(fence.is_empty());
+        assert_eq!(convert("软件", &mut fence), "軟件");
+        // 縮排四格以上是程式碼縮排，不是圍欄
+        assert_eq!(convert("    ```软", &mut fence), "    ```軟");
+        assert!(fence.is_empty());
+    }
+
+    #[test]
+    fn inline_backticks_do_not_open_a_fence() {
+        let mut fence = String::new();
+        assert_eq!(convert("```软``` 软", &mut fence), "```软``` 軟");
+        assert!(fence.is_empty());
+    }
+
+    #[test]
+    fn code_spans_need_matching_run() {
+        assert_eq!(one("用 `软件` 與 ``a ` 软``"), "用 `软件` 與 ``a ` 软``");
+        // 沒有結尾的反引號照常轉換
+        assert_eq!(one("`` 软 `"), "`` 軟 `");
+    }
+
+    #[test]
+    fn urls_and_link_targets_are_kept() {
+        assert_eq!(
+            one("[软件](https://例.com/软件 \"软件\") 见 https://x.org/软，软件"),
+            "[軟件](https://例.com/软件 \"軟件\") 见 https://x.org/软，軟件"
+        );
+        assert_eq!(
+            one("[软件][软] <b class=\"软\">软</b>"),
+            "[軟件][软] <b class=\"软\">軟</b>"
+        );
+    }
+
+    #[test]
+    fn reference_definitions_keep_label_and_url() {
+        assert_eq!(
+            one("[软]: https://x.org/软 \"软件\""),
+            "[软]: https://x.org/软 \"軟件\""
+        );
+        assert_eq!(one("    [软]: 软"), "    [軟]: 軟");
+    }
+}
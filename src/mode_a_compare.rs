@@ -1,33 +1,38 @@
 use colored::Colorize;
-use cw::core;
-use opencc_rust::*;
+use cw::{core, encoding, Format};
+use opencc_rust::DefaultConfig;
 use similar::{ChangeTag, TextDiff};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use unicode_width::UnicodeWidthStr;
 
 const COL: usize = 42;
 
-pub fn run_detailed_compare(_is_phrase: bool, path_a: &str, path_b: &str) {
-    let fa = File::open(path_a).expect("找不到 A");
-    let fb = File::open(path_b).expect("找不到 B");
-    let lines_a: Vec<String> = BufReader::new(fa)
-        .lines()
-        .map(|l| l.unwrap_or_default().replace('\u{feff}', ""))
-        .collect();
-    let lines_b: Vec<String> = BufReader::new(fb)
-        .lines()
-        .map(|l| l.unwrap_or_default().replace('\u{feff}', ""))
-        .collect();
+/// 逐行對比；回傳 `true` 表示無差異且無異常。譯檔的每一行符合 `modes` 任一轉換的結果即算一致。
+/// `format` 為 `None` 時依原檔內容判斷，`lrc_metadata` 與轉換時的設定相同，LRC 標籤才不會被誤判為差異。
+pub fn run_detailed_compare(
+    modes: &[DefaultConfig],
+    path_a: &str,
+    path_b: &str,
+    format: Option<Format>,
+    lrc_metadata: bool,
+) -> cw::Result<bool> {
+    let split = |d: &encoding::Decoded| -> Vec<String> {
+        d.text.lines().map(|l| l.replace('\u{feff}', "")).collect()
+    };
+    let decoded_a = encoding::read_file(path_a, None)?;
+    let lines_a = split(&decoded_a);
+    let lines_b = split(&encoding::read_file(path_b, None)?);
 
-    let issues = core::diagnose_file(path_a, true);
-    let conv_s2t = OpenCC::new(DefaultConfig::S2T).unwrap();
-    let conv_s2twp = OpenCC::new(DefaultConfig::S2TWP).unwrap();
-    let guard = core::RawGuard::new();
+    let format = format.unwrap_or_else(|| Format::resolve(path_a, &decoded_a.text));
+    let issues = core::diagnose_as(&decoded_a, format);
+    let converters = modes
+        .iter()
+        .map(|&m| core::Converter::new(m))
+        .collect::<cw::Result<Vec<_>>>()?;
 
     crate::ui_style::print_compare_header(path_a, path_b);
     let max = std::cmp::max(lines_a.len(), lines_b.len());
     let mut section = String::new();
+    let mut clean = issues.is_empty() && lines_a.len() == lines_b.len();
 
     for i in 0..max {
         let l_idx = i + 1;
@@ -43,12 +48,34 @@ pub fn run_detailed_compare(_is_phrase: bool, path_a: &str, path_b: &str) {
         };
 
         if let (Some(a), Some(b)) = (opt_a, opt_b) {
-            if a.trim().starts_with('[') {
-                section = a.trim().to_string();
-            }
-            let exp_s2t = core::translate_single_line(&conv_s2t, &guard, a, &section);
-            let exp_s2twp = core::translate_single_line(&conv_s2twp, &guard, a, &section);
-            if b.trim() == exp_s2t.trim() || b.trim() == exp_s2twp.trim() || b.trim() == a.trim() {
+            let expected: Vec<String> = match format {
+                Format::Lrc => converters
+                    .iter()
+                    .map(|c| c.convert_lrc_line(a, lrc_metadata))
+                    .collect(),
+                Format::Plain => converters.iter().map(|c| c.convert_text(a)).collect(),
+                Format::Markdown => {
+                    // 圍欄狀態只依原文，每個轉換器都從同一個狀態推進
+                    let before = section.clone();
+                    converters
+                        .iter()
+                        .map(|c| {
+                            section = before.clone();
+                            c.convert_markdown_line(a, &mut section)
+                        })
+                        .collect()
+                }
+                _ => {
+                    if a.trim().starts_with('[') {
+                        section = a.trim().to_string();
+                    }
+                    converters
+                        .iter()
+                        .map(|c| c.convert_line(a, &section))
+                        .collect()
+                }
+            };
+            if b.trim() == a.trim() || expected.iter().any(|e| b.trim() == e.trim()) {
                 println!(
                     "{}{:>4} │ {:<8} │ {} │ {}\x1b[0m",
                     zebra,
@@ -58,13 +85,14 @@ pub fn run_detailed_compare(_is_phrase: bool, path_a: &str, path_b: &str) {
                     crate::ui_style::format_to_width(b, COL)
                 );
             } else {
+                clean = false;
                 print!("{:>4} │ [ DIFF ] │ ", l_idx);
                 print_diff(a, b);
                 println!();
             }
         }
     }
-    if core::needs_trailing_newline_fix(path_a) {
+    if format.is_srt() && core::needs_trailing_newline(&decoded_a.text) {
         println!(
             "{:>4} │ {} │ {} │ {}",
             max + 1,
@@ -74,6 +102,7 @@ pub fn run_detailed_compare(_is_phrase: bool, path_a: &str, path_b: &str) {
         );
     }
     crate::ui_style::print_footnotes(&issues);
+    Ok(clean)
 }
 
 fn print_diff(a: &str, b: &str) {
@@ -1,41 +1,88 @@
+use chrono::Local;
 use cw::report_format::{FileReport, ResultStatus};
+use regex::Regex;
 use reqwest::blocking::{multipart, Client};
 use std::path::Path;
+use std::sync::LazyLock;
 use std::{fs, thread, time::Duration};
 
 const DISCORD_LIMIT: usize = 1950;
+const THREAD_NAME_LIMIT: usize = 100;
+
+static PLACEHOLDER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([a-z_]+)\}").expect("內建樣式"));
+static BLANK_LINES_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\n\s*\n(\s*\n)+").expect("內建樣式"));
+static EPISODE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(S\d{1,2}E\d{1,3}|\bEP?\d{1,3}\b|第\s*\d+\s*[集話话]|\s-\s\d{1,3}\b)")
+        .expect("內建樣式")
+});
+
+/// 發送目標：頻道根目錄、既有討論串，或在論壇頻道新開一篇貼文。
+pub struct Target {
+    pub webhook_url: String,
+    pub thread_id: Option<String>,
+    pub thread_name: Option<String>,
+}
+
+impl Target {
+    pub fn new(
+        webhook_url: &str,
+        thread_id: &str,
+        thread_name_tpl: &str,
+        reports: &[FileReport],
+    ) -> Self {
+        let thread_id = Some(thread_id.trim().to_string()).filter(|s| !s.is_empty());
+        let thread_name = if thread_id.is_none() && !thread_name_tpl.trim().is_empty() {
+            Some(render_thread_name(thread_name_tpl, reports)).filter(|s| !s.is_empty())
+        } else {
+            None
+        };
+        Self {
+            webhook_url: webhook_url.to_string(),
+            thread_id,
+            thread_name,
+        }
+    }
+
+    fn url(&self, thread_id: Option<&str>, wait: bool) -> String {
+        let mut params = Vec::new();
+        if let Some(id) = thread_id {
+            params.push(format!("thread_id={}", id));
+        }
+        if wait {
+            params.push("wait=true".to_string());
+        }
+        if params.is_empty() {
+            return self.webhook_url.clone();
+        }
+        let sep = if self.webhook_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{}{}", self.webhook_url, sep, params.join("&"))
+    }
+}
+
+/// 訊息內容設定：自訂開場白、模板檔內容與顯示選項。
+pub struct MessageOptions<'a> {
+    pub intro: Option<&'a str>,
+    pub template: Option<&'a str>,
+    pub mention_id: &'a str,
+    pub show_stats: bool,
+    pub show_errors: bool,
+    pub duration: Duration,
+}
 
 pub fn execute(
-    webhook_url: &str,
-    _intro_text: Option<&str>,
-    mention_id: &str,
+    target: &Target,
+    msg: &MessageOptions,
     interval: u64,
-    show_stats: bool,
-    show_errors: bool,
     reports: &[FileReport],
 ) -> Result<(), String> {
     let client = Client::new();
-    let mut full_content = String::new();
-
-    if show_stats {
-        for r in reports {
-            full_content.push_str(&format!(
-                "`{}` (變動: {} 行)\n",
-                r.input_name,
-                r.translated_pairs.len()
-            ));
-        }
-    }
-    if show_errors {
-        for r in reports {
-            for issue in &r.issues {
-                full_content.push_str(&format!("! {}\n", issue.message));
-            }
-        }
-    }
-    if !mention_id.is_empty() {
-        full_content.push_str(&format!("<@{}>", mention_id));
-    }
+    let full_content = render_message(msg, reports);
 
     let chunks = split_content_safely(&full_content);
     let chunks_to_send = if chunks.is_empty() {
@@ -44,19 +91,25 @@ pub fn execute(
         chunks
     };
 
+    let mut thread_id = target.thread_id.clone();
     for (i, chunk) in chunks_to_send.iter().enumerate() {
         let is_last = i == chunks_to_send.len() - 1;
         let mut form = multipart::Form::new().text("content", chunk.clone());
+        // 論壇貼文只在第一則訊息建立，其後分段都回貼到新討論串
+        let new_post = match thread_id {
+            None => target.thread_name.clone(),
+            Some(_) => None,
+        };
+        let create_post = new_post.is_some();
+        if let Some(name) = new_post {
+            form = form.text("thread_name", name);
+        }
         if is_last {
             let mut count = 0;
             for r in reports {
                 if r.status != ResultStatus::ConvertError {
                     if let Ok(data) = fs::read(&r.output_name) {
-                        let name = Path::new(&r.output_name)
-                            .file_name()
-                            .unwrap()
-                            .to_string_lossy()
-                            .to_string();
+                        let name = file_name_of(&r.output_name);
                         form = form.part(
                             format!("file{}", count),
                             multipart::Part::bytes(data).file_name(name),
@@ -69,11 +122,23 @@ pub fn execute(
                 }
             }
         }
-        let _ = client
-            .post(webhook_url)
+        let resp = client
+            .post(target.url(thread_id.as_deref(), create_post))
             .multipart(form)
             .send()
             .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("Discord 回應 {}", resp.status()));
+        }
+        if create_post {
+            let body = resp.text().map_err(|e| e.to_string())?;
+            thread_id = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["channel_id"].as_str().map(str::to_string));
+            if thread_id.is_none() {
+                return Err("無法取得新貼文的討論串 ID".to_string());
+            }
+        }
         if !is_last {
             thread::sleep(Duration::from_secs(interval));
         }
@@ -88,8 +153,12 @@ fn split_content_safely(text: &str) -> Vec<String> {
         return chunks;
     }
     while remaining.chars().count() > DISCORD_LIMIT {
-        let mut split_pos = DISCORD_LIMIT;
-        let current_chunk = remaining.chars().take(DISCORD_LIMIT).collect::<String>();
+        // DISCORD_LIMIT 是字元數，須換成位元組位置才能切在字元邊界上
+        let mut split_pos = remaining
+            .char_indices()
+            .nth(DISCORD_LIMIT)
+            .map_or(remaining.len(), |(i, _)| i);
+        let current_chunk = &remaining[..split_pos];
         if let Some(pos) = current_chunk.rfind('\n') {
             split_pos = pos;
         } else if let Some(pos) = current_chunk.rfind(' ') {
@@ -104,3 +173,236 @@ fn split_content_safely(text: &str) -> Vec<String> {
     }
     chunks
 }
+
+/// 未指定模板時沿用舊版排版：統計、異常、最後提及。
+fn default_template(msg: &MessageOptions) -> String {
+    let mut tpl = String::from("{intro}\n");
+    if msg.show_stats {
+        tpl.push_str("{files}\n");
+    }
+    if msg.show_errors {
+        tpl.push_str("{issues}\n");
+    }
+    tpl.push_str("{mention}");
+    tpl
+}
+
+/// 依模板組出訊息，除共用欄位外另支援
+/// {intro} {files} {file_names} {changes} {issues} {issue_count} {mention} {duration}。
+pub fn render_message(msg: &MessageOptions, reports: &[FileReport]) -> String {
+    let tpl = msg
+        .template
+        .map(str::to_string)
+        .unwrap_or_else(|| default_template(msg));
+    let files: String = reports
+        .iter()
+        .map(|r| format!("`{}` (變動: {} 行)\n", r.input_name, r.changed_lines))
+        .collect();
+    let issues: String = reports
+        .iter()
+        .flat_map(|r| &r.issues)
+        .map(|i| format!("! {}\n", i.message))
+        .collect();
+    let names: Vec<String> = reports
+        .iter()
+        .map(|r| file_name_of(&r.input_name))
+        .collect();
+    let mention = if msg.mention_id.is_empty() {
+        String::new()
+    } else {
+        format!("<@{}>", msg.mention_id)
+    };
+    let mut values = common_values(reports);
+    values.extend([
+        ("intro", msg.intro.unwrap_or("").to_string()),
+        ("files", files.trim_end().to_string()),
+        ("file_names", names.join(", ")),
+        (
+            "changes",
+            reports
+                .iter()
+                .map(|r| r.changed_lines)
+                .sum::<usize>()
+                .to_string(),
+        ),
+        ("issues", issues.trim_end().to_string()),
+        (
+            "issue_count",
+            reports
+                .iter()
+                .map(|r| r.issues.len())
+                .sum::<usize>()
+                .to_string(),
+        ),
+        ("mention", mention),
+        ("duration", format!("{:.2?}", msg.duration)),
+    ]);
+    let text = fill(&tpl, &values);
+    // 空欄位留下的多餘空行一併收掉
+    BLANK_LINES_RE.replace_all(text.trim(), "\n\n").to_string()
+}
+
+/// 以模板產生討論串名稱，可用 {show} {episode} {file} {count} {date}。
+pub fn render_thread_name(tpl: &str, reports: &[FileReport]) -> String {
+    let name = fill(tpl, &common_values(reports));
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    name.chars().take(THREAD_NAME_LIMIT).collect()
+}
+
+/// 訊息與討論串名稱共用的欄位
+fn common_values(reports: &[FileReport]) -> Vec<(&'static str, String)> {
+    let file = reports
+        .first()
+        .map(|r| file_name_of(&r.input_name))
+        .unwrap_or_default();
+    let (show, episode) = parse_release_name(&file);
+    vec![
+        ("show", show),
+        ("episode", episode),
+        ("file", file),
+        ("count", reports.len().to_string()),
+        ("date", Local::now().format("%Y-%m-%d").to_string()),
+    ]
+}
+
+/// 一次掃過模板代入所有欄位；代入的值不再展開 (檔名含 `{issues}` 也原樣保留)，
+/// 未知的 `{名稱}` 原樣保留。
+fn fill(tpl: &str, values: &[(&str, String)]) -> String {
+    PLACEHOLDER_RE
+        .replace_all(tpl, |c: &regex::Captures| {
+            values
+                .iter()
+                .find(|(k, _)| *k == &c[1])
+                .map_or_else(|| c[0].to_string(), |(_, v)| v.clone())
+        })
+        .into_owned()
+}
+
+fn file_name_of(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// 從檔名拆出劇名與集數，例如 `Show.Name.S01E03.srt` → ("Show Name", "S01E03")。
+pub fn parse_release_name(file: &str) -> (String, String) {
+    let stem = strip_sub_exts(file);
+    let tidy = |s: &str| {
+        s.replace(['.', '_'], " ")
+            .trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '[' || c == ']')
+            .to_string()
+    };
+    match EPISODE_RE.find(&stem) {
+        Some(m) => (tidy(&stem[..m.start()]), tidy(m.as_str())),
+        None => (tidy(&stem), String::new()),
+    }
+}
+
+fn strip_sub_exts(name: &str) -> String {
+    let mut n = name;
+    loop {
+        let lower = n.to_lowercase();
+        match [".srt", ".ass", ".ssa", ".vtt", ".txt", ".tmp"]
+            .iter()
+            .find(|e| lower.ends_with(*e))
+        {
+            Some(e) => n = &n[..n.len() - e.len()],
+            None => return n.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cw::report_format::SubtitleIssue;
+    use std::path::PathBuf;
+
+    fn report(input: &str, issues: &[&str]) -> FileReport {
+        FileReport {
+            input_name: input.to_string(),
+            output_name: format!("{}.txt", input),
+            temp_log_path: PathBuf::new(),
+            status: ResultStatus::Success,
+            issues: issues
+                .iter()
+                .map(|m| SubtitleIssue {
+                    line: 1,
+                    message: m.to_string(),
+                })
+                .collect(),
+            translated_pairs: Vec::new(),
+            changed_lines: 3,
+            duration: Duration::from_millis(5),
+        }
+    }
+
+    fn message(template: &str) -> MessageOptions<'_> {
+        MessageOptions {
+            intro: Some("第 {count} 批"),
+            template: Some(template),
+            mention_id: "42",
+            show_stats: true,
+            show_errors: true,
+            duration: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn placeholders_in_values_are_not_expanded() {
+        let reports = [report("subs/Show.{issues}.S01E02.srt", &["時間軸錯誤"])];
+        let text = render_message(
+            &message("{intro} | {file} | {episode} | {issue_count} | {mention} | {unknown}"),
+            &reports,
+        );
+        assert_eq!(
+            text,
+            "第 {count} 批 | Show.{issues}.S01E02.srt | S01E02 | 1 | <@42> | {unknown}"
+        );
+    }
+
+    #[test]
+    fn thread_name_uses_release_name() {
+        let reports = [
+            report("in/My.Show.S02E10.srt", &[]),
+            report("in/b.srt", &[]),
+        ];
+        assert_eq!(
+            render_thread_name("{show} {episode} ({count})", &reports),
+            "My Show S02E10 (2)"
+        );
+    }
+
+    #[test]
+    fn parse_release_name_variants() {
+        assert_eq!(
+            parse_release_name("Show.Name.S01E03.srt.txt"),
+            ("Show Name".to_string(), "S01E03".to_string())
+        );
+        assert_eq!(
+            parse_release_name("某劇_第 12 集.ass"),
+            ("某劇".to_string(), "第 12 集".to_string())
+        );
+        assert_eq!(
+            parse_release_name("movie.srt"),
+            ("movie".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn empty_fields_do_not_leave_blank_runs() {
+        let reports = [report("a.srt", &[])];
+        let text = render_message(&message("{intro}\n\n{issues}\n\n\n{mention}"), &reports);
+        assert_eq!(text, "第 {count} 批\n\n<@42>");
+    }
+
+    #[test]
+    fn long_cjk_text_splits_on_char_boundaries() {
+        let text = "軟體字幕".repeat(DISCORD_LIMIT);
+        let chunks = split_content_safely(&text);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| c.chars().count() <= DISCORD_LIMIT));
+        assert_eq!(chunks.concat(), text);
+    }
+}
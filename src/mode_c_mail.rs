@@ -0,0 +1,351 @@
+use cw::core::Config;
+use cw::report_format::{FileReport, ResultStatus};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, time::Duration};
+use zip::write::SimpleFileOptions;
+
+const ZIP_NAME: &str = "cw_outputs.zip";
+
+pub fn execute(cfg: &Config, reports: &[FileReport], total: Duration) -> Result<(), String> {
+    let from: Mailbox = cfg
+        .smtp_from
+        .parse()
+        .map_err(|e| format!("寄件者格式錯誤: {}", e))?;
+    let mut builder = Message::builder()
+        .from(from)
+        .subject(build_subject(reports));
+    let mut has_to = false;
+    for addr in cfg.smtp_to.split([',', ';']).map(str::trim) {
+        if addr.is_empty() {
+            continue;
+        }
+        let to: Mailbox = addr
+            .parse()
+            .map_err(|e| format!("收件者格式錯誤 {}: {}", addr, e))?;
+        builder = builder.to(to);
+        has_to = true;
+    }
+    if !has_to {
+        return Err("未設定 smtp_to".to_string());
+    }
+
+    let (attachments, skipped) = collect_attachments(cfg, reports)?;
+    let mut body = build_body(reports, total);
+    if !skipped.is_empty() {
+        body.push_str("\n附件超過大小限制，未隨信附上：\n");
+        for s in &skipped {
+            body.push_str(&format!("  - {}\n", s));
+        }
+    }
+
+    let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(body));
+    for (name, mime, data) in attachments {
+        let ct = ContentType::parse(&mime).map_err(|e| e.to_string())?;
+        parts = parts.singlepart(Attachment::new(name).body(data, ct));
+    }
+    let email = builder.multipart(parts).map_err(|e| e.to_string())?;
+
+    let transport = match cfg.smtp_security.as_str() {
+        "none" => SmtpTransport::builder_dangerous(&cfg.smtp_host),
+        "tls" => SmtpTransport::relay(&cfg.smtp_host).map_err(|e| e.to_string())?,
+        _ => SmtpTransport::starttls_relay(&cfg.smtp_host).map_err(|e| e.to_string())?,
+    };
+    let mut transport = transport.port(cfg.smtp_port);
+    if !cfg.smtp_user.is_empty() {
+        transport = transport.credentials(Credentials::new(
+            cfg.smtp_user.clone(),
+            cfg.smtp_password.clone(),
+        ));
+    }
+    transport
+        .build()
+        .send(&email)
+        .map_err(|e| format!("SMTP 發送失敗: {}", e))?;
+    Ok(())
+}
+
+fn build_subject(reports: &[FileReport]) -> String {
+    let issues: usize = reports.iter().map(|r| r.issues.len()).sum();
+    if issues > 0 {
+        format!(
+            "[CW] 字幕轉換完成：{} 個檔案（異常 {} 處）",
+            reports.len(),
+            issues
+        )
+    } else {
+        format!("[CW] 字幕轉換完成：{} 個檔案", reports.len())
+    }
+}
+
+fn build_body(reports: &[FileReport], total: Duration) -> String {
+    let mut body = format!(
+        "CW 字幕轉換批次報告\n檔案數: {} | 總耗時: {:?}\n{}\n",
+        reports.len(),
+        total,
+        "-".repeat(40)
+    );
+    for r in reports {
+        let icon = if r.status == ResultStatus::Success {
+            "[OK]"
+        } else {
+            "[⚠]"
+        };
+        body.push_str(&format!(
+            "{} {} -> {}\n     變動: {} 行 | 異常: {} 處 | 耗時: {:?}\n",
+            icon,
+            r.input_name,
+            r.output_name,
+            r.changed_lines,
+            r.issues.len(),
+            r.duration
+        ));
+        for iss in &r.issues {
+            body.push_str(&format!("     ! L{:03} {}\n", iss.line, iss.message));
+        }
+    }
+    body
+}
+
+type Part = (String, String, Vec<u8>);
+
+fn collect_attachments(
+    cfg: &Config,
+    reports: &[FileReport],
+) -> Result<(Vec<Part>, Vec<String>), String> {
+    let limit = cfg.mail_max_size_mb * 1024 * 1024;
+    let mut paths = Vec::new();
+    let mut contents = Vec::new();
+    for r in reports {
+        if r.status == ResultStatus::ConvertError {
+            continue;
+        }
+        if let Ok(data) = fs::read(&r.output_name) {
+            paths.push(Path::new(&r.output_name));
+            contents.push(data);
+        }
+    }
+    let files: Vec<(String, Vec<u8>)> =
+        attachment_names(&paths).into_iter().zip(contents).collect();
+    // 成果檔皆以同一設定寫出
+    let mime = format!("text/plain; charset={}", cfg.output_encoding.charset());
+
+    let mut parts = Vec::new();
+    let mut skipped = Vec::new();
+    if cfg.mail_zip {
+        if files.is_empty() {
+            return Ok((parts, skipped));
+        }
+        let data = zip_files(&files).map_err(|e| format!("壓縮失敗: {}", e))?;
+        if data.len() as u64 <= limit {
+            parts.push((ZIP_NAME.to_string(), "application/zip".to_string(), data));
+        } else {
+            skipped.push(format!("{} ({})", ZIP_NAME, format_size(data.len())));
+        }
+        return Ok((parts, skipped));
+    }
+
+    let mut used = 0u64;
+    for (name, data) in files {
+        let size = data.len() as u64;
+        if used + size <= limit {
+            used += size;
+            // 附件名稱不能含路徑分隔
+            parts.push((name.replace('/', "_"), mime.clone(), data));
+        } else {
+            skipped.push(format!("{} ({})", name, format_size(data.len())));
+        }
+    }
+    Ok((parts, skipped))
+}
+
+/// 附件與壓縮檔內的名稱：取所有成果檔共同上層目錄之下的相對路徑，
+/// 不同目錄的同名檔案不會互相覆蓋；全在同一目錄時即為檔名。
+fn attachment_names(paths: &[&Path]) -> Vec<String> {
+    let absolute: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()))
+        .collect();
+    let mut root: Option<PathBuf> = absolute
+        .first()
+        .and_then(|p| p.parent())
+        .map(Path::to_path_buf);
+    for p in &absolute {
+        while let Some(r) = root.as_ref().filter(|r| !p.starts_with(r)) {
+            root = r.parent().map(Path::to_path_buf);
+        }
+    }
+    absolute
+        .iter()
+        .zip(paths)
+        .map(|(abs, orig)| {
+            root.as_ref()
+                .and_then(|r| abs.strip_prefix(r).ok())
+                .map(|rel| {
+                    rel.components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                })
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| orig.to_string_lossy().to_string())
+        })
+        .collect()
+}
+
+fn zip_files(files: &[(String, Vec<u8>)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zw = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, data) in files {
+        zw.start_file(name.as_str(), opts)?;
+        zw.write_all(data)?;
+    }
+    Ok(zw.finish()?.into_inner())
+}
+
+fn format_size(len: usize) -> String {
+    format!("{:.1} MB", len as f64 / 1024.0 / 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cw::core::ConfigRoots;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// 最小的本機 SMTP 接收端：一律回應成功，回傳收到的 DATA 內容。
+    fn smtp_sink() -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut out = stream;
+            out.write_all(b"220 sink ESMTP\r\n").unwrap();
+            let (mut data, mut in_data, mut line) = (String::new(), false, String::new());
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        out.write_all(b"250 queued\r\n").unwrap();
+                    } else {
+                        data.push_str(&line);
+                    }
+                    continue;
+                }
+                let cmd = line.to_ascii_uppercase();
+                if cmd.starts_with("QUIT") {
+                    out.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                }
+                in_data = cmd.starts_with("DATA");
+                out.write_all(if in_data {
+                    b"354 go\r\n"
+                } else {
+                    b"250 OK\r\n"
+                })
+                .unwrap();
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    /// 兩個不同目錄的同名成果檔，以 GBK 寫出
+    fn fixture(name: &str) -> (PathBuf, Vec<FileReport>) {
+        let dir = crate::tests::scratch(&format!("mail_{}", name));
+        let mut reports = Vec::new();
+        for sub in ["a", "b"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            let out = dir.join(sub).join("ep1.srt.txt");
+            let (gbk, _, _) = encoding_rs::GBK.encode("1\n00:00:01,000 --> 00:00:02,000\n軟件\n\n");
+            fs::write(&out, gbk).unwrap();
+            reports.push(FileReport {
+                input_name: format!("{}/ep1.srt", sub),
+                output_name: out.to_string_lossy().to_string(),
+                temp_log_path: PathBuf::new(),
+                status: ResultStatus::Success,
+                issues: Vec::new(),
+                translated_pairs: Vec::new(),
+                changed_lines: 1,
+                duration: Duration::from_millis(1),
+            });
+        }
+        (dir, reports)
+    }
+
+    /// 只用內建預設與下列覆寫，不讀本機的設定檔與 `CW_*` 環境變數
+    fn config(port: u16, zip: bool) -> Config {
+        let set = |k: &str, v: &str| (k.to_string(), v.to_string());
+        Config::load_from(
+            &ConfigRoots::default(),
+            &[
+                set("smtp_host", "127.0.0.1"),
+                set("smtp_port", &port.to_string()),
+                set("smtp_security", "none"),
+                set("smtp_from", "cw@example.com"),
+                set("smtp_to", "team@example.com"),
+                set("output_encoding", "gbk"),
+                set("mail_zip", if zip { "true" } else { "false" }),
+            ],
+        )
+    }
+
+    #[test]
+    fn sends_attachments_with_output_charset() {
+        let (dir, reports) = fixture("send");
+        let (port, sink) = smtp_sink();
+        execute(&config(port, false), &reports, Duration::from_secs(1)).unwrap();
+        let data = sink.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(data.contains("Subject:"));
+        assert!(data.contains("charset=gbk"));
+        assert!(data.contains("a_ep1.srt.txt"));
+        assert!(data.contains("b_ep1.srt.txt"));
+    }
+
+    #[test]
+    fn zip_keeps_same_named_files_apart() {
+        let (dir, reports) = fixture("zip");
+        let (parts, skipped) = collect_attachments(&config(25, true), &reports).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(skipped.is_empty());
+        let [(name, mime, data)] = parts.as_slice() else {
+            panic!("應只有一個壓縮檔");
+        };
+        assert_eq!(
+            (name.as_str(), mime.as_str()),
+            (ZIP_NAME, "application/zip")
+        );
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, ["a/ep1.srt.txt", "b/ep1.srt.txt"]);
+        let mut entry = Vec::new();
+        archive
+            .by_name("a/ep1.srt.txt")
+            .unwrap()
+            .read_to_end(&mut entry)
+            .unwrap();
+        assert_eq!(
+            encoding_rs::GBK.decode(&entry).0,
+            "1\n00:00:01,000 --> 00:00:02,000\n軟件\n\n"
+        );
+    }
+
+    #[test]
+    fn attachment_names_fall_back_to_file_name() {
+        let names = attachment_names(&[Path::new("out/x.srt.txt"), Path::new("out/y.srt.txt")]);
+        assert_eq!(names, ["x.srt.txt", "y.srt.txt"]);
+    }
+}
@@ -0,0 +1,171 @@
+// ==========================================
+// CW Mojibake Module
+// ==========================================
+//
+// 偵測常見的重複解碼亂碼 (例如 UTF-8 被當成 GBK 讀取後再存檔) 與 U+FFFD 取代字元，
+// 並嘗試反轉編碼鏈修復。
+
+use encoding_rs::{Encoding, BIG5, GBK, UTF_8, WINDOWS_1252};
+
+/// 一種重複解碼鏈：原文以 `original` 編碼存檔，卻被當成 `misread` 讀取。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chain {
+    pub original: &'static Encoding,
+    pub misread: &'static Encoding,
+}
+
+impl std::fmt::Display for Chain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} 被當成 {}", self.original.name(), self.misread.name())
+    }
+}
+
+/// 依常見程度排列；修復時取第一個成功的鏈。
+pub const CHAINS: &[Chain] = &[
+    Chain {
+        original: UTF_8,
+        misread: GBK,
+    },
+    Chain {
+        original: UTF_8,
+        misread: WINDOWS_1252,
+    },
+    Chain {
+        original: UTF_8,
+        misread: BIG5,
+    },
+    Chain {
+        original: GBK,
+        misread: WINDOWS_1252,
+    },
+    Chain {
+        original: BIG5,
+        misread: WINDOWS_1252,
+    },
+];
+
+/// 最多反轉幾層 (例如被錯讀兩次)
+const MAX_DEPTH: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// 含有 U+FFFD，原始內容已遺失，無法修復
+    Replacement,
+    /// 可反轉的亂碼；`chains` 依反轉順序列出，`repaired` 為修復結果
+    Mojibake {
+        chains: Vec<Chain>,
+        repaired: String,
+    },
+}
+
+/// 檢查單行；正常文字回傳 `None`。
+pub fn scan_line(line: &str) -> Option<Finding> {
+    if let Some((repaired, chains)) = repair_line(line) {
+        return Some(Finding::Mojibake { chains, repaired });
+    }
+    line.contains('\u{FFFD}').then_some(Finding::Replacement)
+}
+
+/// 嘗試反轉重複解碼；只有整行都能無損往返且結果更短時才視為亂碼。
+pub fn repair_line(line: &str) -> Option<(String, Vec<Chain>)> {
+    let mut current = line.to_string();
+    let mut applied = Vec::new();
+    for _ in 0..MAX_DEPTH {
+        match CHAINS
+            .iter()
+            .find_map(|c| undo(&current, c).map(|t| (t, *c)))
+        {
+            Some((text, chain)) => {
+                current = text;
+                applied.push(chain);
+            }
+            None => break,
+        }
+    }
+    (!applied.is_empty()).then_some((current, applied))
+}
+
+fn undo(text: &str, chain: &Chain) -> Option<String> {
+    let non_ascii = text.chars().filter(|c| !c.is_ascii()).count();
+    // 雙位元組字集的巧合往返機率隨長度遞減，太短的行不判定
+    let min = if chain.misread == WINDOWS_1252 { 2 } else { 3 };
+    if non_ascii < min || text.contains('\u{FFFD}') {
+        return None;
+    }
+    let (bytes, _, unmappable) = chain.misread.encode(text);
+    if unmappable {
+        return None;
+    }
+    let repaired = chain
+        .original
+        .decode_without_bom_handling_and_without_replacement(&bytes)?;
+    let shorter = repaired.chars().count() < text.chars().count();
+    let meaningful = repaired.chars().any(|c| !c.is_ascii() && !c.is_control());
+    (shorter && meaningful).then(|| repaired.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以 `chain` 製造亂碼：原文以 original 編碼，再以 misread 解讀
+    fn garble(text: &str, chain: &Chain) -> String {
+        let (bytes, _, _) = chain.original.encode(text);
+        chain
+            .misread
+            .decode_without_bom_handling(&bytes)
+            .0
+            .into_owned()
+    }
+
+    #[test]
+    fn reverses_single_chains() {
+        for (text, chain) in [
+            ("這是繁體中文字幕", &CHAINS[0]),
+            ("這是繁體中文字幕", &CHAINS[1]),
+            ("这是简体中文字幕", &CHAINS[3]),
+        ] {
+            let (repaired, chains) = repair_line(&garble(text, chain)).unwrap();
+            assert_eq!(repaired, text, "{}", chain);
+            assert_eq!(chains, [*chain]);
+        }
+    }
+
+    #[test]
+    fn every_chain_is_flagged() {
+        // Big5 與 GBK 的位元組範圍重疊，修復結果不一定正確，但都要能偵測出來
+        for chain in CHAINS {
+            let garbled = garble("這是繁體中文字幕", chain);
+            assert!(scan_line(&garbled).is_some(), "{}", chain);
+        }
+    }
+
+    #[test]
+    fn reverses_double_misread() {
+        let first = garble("軟體更新", &CHAINS[0]);
+        let twice = garble(&first, &CHAINS[1]);
+        let (repaired, chains) = repair_line(&twice).unwrap();
+        assert_eq!(repaired, "軟體更新");
+        assert_eq!(chains, [CHAINS[1], CHAINS[0]]);
+    }
+
+    #[test]
+    fn normal_text_is_left_alone() {
+        for line in [
+            "軟體更新",
+            "Café au lait",
+            "00:00:01,000 --> 00:00:02,000",
+            "é",
+            "",
+        ] {
+            assert_eq!(scan_line(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn replacement_characters_cannot_be_repaired() {
+        assert_eq!(scan_line("軟\u{FFFD}體"), Some(Finding::Replacement));
+        let garbled = format!("{}\u{FFFD}", garble("軟體更新", &CHAINS[0]));
+        assert_eq!(scan_line(&garbled), Some(Finding::Replacement));
+    }
+}
@@ -0,0 +1,707 @@
+// ==========================================
+// CW Subtitle Module
+// ==========================================
+//
+// 以時間軸為單位的字幕讀寫：SRT、MicroDVD、SubViewer/SBV、SAMI、TTML。
+// 供格式互轉與時間軸診斷使用；文字轉換仍由 `core::Converter` 逐行處理。
+
+use crate::error::{Error, Result};
+use crate::format::Format;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// 可作為輸出的格式名稱；`same` 表示沿用輸入格式。
+pub const OUTPUT_FORMATS: &[&str] = &["same", "srt", "microdvd", "sbv", "smi", "ttml"];
+
+/// MicroDVD 沒有檔頭指定影格率時的預設值
+pub const DEFAULT_FPS: f64 = 23.976;
+
+/// 沒有結束時間的最後一句 (SAMI、MicroDVD `{}`) 顯示多久
+const LAST_CUE_MS: u64 = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// 時間軸所在的來源行號 (1 起算)
+    pub line: usize,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// 字幕文字，多行以 `\n` 分隔；斜體以 `<i>…</i>` 表示
+    pub text: String,
+}
+
+/// 支援時間軸讀寫的格式
+pub fn supports(format: Format) -> bool {
+    matches!(
+        format,
+        Format::Srt | Format::MicroDvd | Format::Sbv | Format::Sami | Format::Ttml
+    )
+}
+
+/// 依格式讀取時間軸；`fps` 只用於 MicroDVD，`None` 時取檔頭 `{1}{1}23.976` 或預設值。
+pub fn parse(text: &str, format: Format, fps: Option<f64>) -> Result<Vec<Cue>> {
+    if format == Format::Ttml {
+        return crate::ttml::parse(text.trim_start_matches('\u{feff}'));
+    }
+    let mut reader = CueReader::new(format, fps)?;
+    let mut cues = Vec::new();
+    for line in text.lines() {
+        reader.push(line, &mut cues)?;
+    }
+    reader.finish(&mut cues)?;
+    Ok(cues)
+}
+
+/// 依格式輸出；換行一律為 `\n`。
+pub fn render(cues: &[Cue], format: Format, fps: f64) -> Result<String> {
+    match format {
+        Format::Srt => Ok(render_srt(cues)),
+        Format::Sbv => Ok(render_sbv(cues)),
+        Format::MicroDvd => Ok(render_microdvd(cues, fps)),
+        Format::Sami => Ok(render_sami(cues)),
+        Format::Ttml => Ok(crate::ttml::render(cues)),
+        other => Err(Error::parse(0, 0, format!("不支援輸出 {} 格式", other))),
+    }
+}
+
+/// 實際使用的影格率：指定值優先，其次 MicroDVD 檔頭，否則為 `DEFAULT_FPS`。
+pub fn frame_rate(text: &str, fps: Option<f64>) -> f64 {
+    fps.or_else(|| microdvd_header_fps(text))
+        .unwrap_or(DEFAULT_FPS)
+}
+
+fn microdvd_header_fps(text: &str) -> Option<f64> {
+    header_fps(text.lines().find(|l| !l.trim().is_empty())?)
+}
+
+/// `{1}{1}23.976` 檔頭行指定的影格率
+fn header_fps(line: &str) -> Option<f64> {
+    let rest = line.trim().strip_prefix("{1}{1}")?;
+    rest.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|f| *f > 0.0 && *f < 1000.0)
+}
+
+// --- [ 時間格式 ] ---
+
+/// 解析 `[H:]MM:SS[,.]mmm`；小數不足三位依位數換算。
+fn parse_clock(s: &str) -> Option<u64> {
+    let (hms, frac) = match s.trim().rsplit_once([',', '.']) {
+        Some((a, b)) => (a, b),
+        None => (s.trim(), "0"),
+    };
+    let mut parts = hms.split(':').rev();
+    let sec: u64 = parts.next()?.parse().ok()?;
+    let min: u64 = parts.next()?.parse().ok()?;
+    let hour: u64 = match parts.next() {
+        Some(h) => h.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() || sec >= 60 || min >= 60 || frac.len() > 3 {
+        return None;
+    }
+    let ms: u64 = format!("{:0<3}", frac).parse().ok()?;
+    Some(((hour * 60 + min) * 60 + sec) * 1000 + ms)
+}
+
+fn clock(ms: u64, sep: char, hour_width: usize) -> String {
+    format!(
+        "{:0hw$}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        sep,
+        ms % 1000,
+        hw = hour_width
+    )
+}
+
+/// 欄位在行內的欄號 (1 起算，以字元計)
+fn column(line: &str, field: &str) -> usize {
+    line.find(field)
+        .map(|b| line[..b].chars().count() + 1)
+        .unwrap_or(1)
+}
+
+// --- [ 逐行讀取 ] ---
+
+/// 逐行讀取時間軸 (SRT、SBV、MicroDVD、SAMI)：`parse` 與串流診斷共用，
+/// 只保留尚未結束的一句，記憶體與檔案大小無關。
+pub struct CueReader {
+    format: Format,
+    /// MicroDVD 影格率；`None` 時於第一個非空行取檔頭或預設值
+    fps: Option<f64>,
+    line: usize,
+    /// 尚未輸出的一句：SRT / SBV 等空行，MicroDVD / SAMI 等下一句開始才知道結束時間
+    open: Option<Cue>,
+    /// SRT / SBV：正在讀取內文 (尚未遇到空行)
+    in_body: bool,
+    /// SRT：空行後的純數字行，下一行是時間軸才知道它是編號還是內文
+    held: Option<String>,
+    /// SAMI：目前的 SYNC (行號, Start) 與其後累積的內容
+    sync: Option<(usize, u64)>,
+    content: String,
+    /// SAMI：已讀到 `</BODY>`
+    done: bool,
+}
+
+impl CueReader {
+    /// TTML 為 XML，不逐行讀取 (見 `ttml::parse`)。
+    pub fn new(format: Format, fps: Option<f64>) -> Result<Self> {
+        if !matches!(
+            format,
+            Format::Srt | Format::Sbv | Format::MicroDvd | Format::Sami
+        ) {
+            return Err(Error::parse(0, 0, format!("不支援讀取 {} 時間軸", format)));
+        }
+        Ok(Self {
+            format,
+            fps,
+            line: 0,
+            open: None,
+            in_body: false,
+            held: None,
+            sync: None,
+            content: String::new(),
+            done: false,
+        })
+    }
+
+    /// 讀入一行 (不含換行)；已確定結束時間的句子依序加入 `out`。
+    pub fn push(&mut self, raw: &str, out: &mut Vec<Cue>) -> Result<()> {
+        self.line += 1;
+        let raw = if self.line == 1 {
+            raw.trim_start_matches('\u{feff}')
+        } else {
+            raw
+        };
+        match self.format {
+            Format::Srt | Format::Sbv => self.push_block(raw, out),
+            Format::MicroDvd => self.push_microdvd(raw, out),
+            _ => self.push_sami(raw, out),
+        }
+    }
+
+    /// 輸入結束：輸出最後一句。
+    pub fn finish(mut self, out: &mut Vec<Cue>) -> Result<()> {
+        match self.format {
+            Format::Srt | Format::Sbv => self.close_block(out),
+            Format::MicroDvd => {
+                if let Some(mut last) = self.open.take() {
+                    last.end_ms = last.start_ms + LAST_CUE_MS;
+                    out.push(last);
+                }
+            }
+            _ => {
+                if self.sync.is_none() {
+                    return Err(Error::parse(0, 0, "找不到 <SYNC Start=…> 時間軸"));
+                }
+                self.close_sync(out);
+                if let Some(mut last) = self.open.take() {
+                    last.end_ms = last.start_ms + LAST_CUE_MS;
+                    out.push(last);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // --- SRT / SubViewer (SBV)：時間軸一行，內文到空行為止 ---
+
+    fn push_block(&mut self, raw: &str, out: &mut Vec<Cue>) -> Result<()> {
+        let l = raw.trim();
+        if self.in_body {
+            if l.is_empty() {
+                self.in_body = false;
+                // SBV 以空行結束一句；SRT 要看到下一個時間軸才確定 (內文可能含空行)
+                if self.format != Format::Srt {
+                    self.close_block(out);
+                }
+            } else {
+                self.append(raw);
+            }
+            return Ok(());
+        }
+        if l.is_empty() {
+            return Ok(());
+        }
+        let (start, end) = if self.format == Format::Srt {
+            if !l.contains("-->") && self.open.is_some() {
+                // 空行後不是時間軸：數字行先保留，其餘為上一句的延續
+                if l.chars().all(|c| c.is_ascii_digit()) {
+                    if let Some(prev) = self.held.replace(raw.trim_end().to_string()) {
+                        self.append(&prev);
+                    }
+                } else {
+                    if let Some(prev) = self.held.take() {
+                        self.append(&prev);
+                    }
+                    self.append(raw);
+                    self.in_body = true;
+                }
+                return Ok(());
+            }
+            if l.chars().all(|c| c.is_ascii_digit()) {
+                return Ok(());
+            }
+            self.held = None;
+            self.srt_timing(raw)?
+        } else {
+            self.sbv_timing(raw)?
+        };
+        self.close_block(out);
+        self.open = Some(Cue {
+            line: self.line,
+            start_ms: start,
+            end_ms: end,
+            text: String::new(),
+        });
+        self.in_body = true;
+        Ok(())
+    }
+
+    fn append(&mut self, raw: &str) {
+        if let Some(cue) = self.open.as_mut() {
+            if !cue.text.is_empty() {
+                cue.text.push('\n');
+            }
+            cue.text.push_str(raw.trim_end());
+        }
+    }
+
+    fn close_block(&mut self, out: &mut Vec<Cue>) {
+        if let Some(mut cue) = self.open.take() {
+            // SBV 的 [br] 為行內換行
+            if self.format == Format::Sbv {
+                cue.text = cue.text.replace("[br]", "\n");
+            }
+            out.push(cue);
+        }
+    }
+
+    fn srt_timing(&self, raw: &str) -> Result<(u64, u64)> {
+        let Some((a, b)) = raw.trim().split_once("-->") else {
+            return Err(Error::parse(
+                self.line,
+                1,
+                "預期時間軸 `00:00:00,000 --> 00:00:00,000`",
+            ));
+        };
+        let start = parse_clock(a).ok_or_else(|| Error::parse(self.line, 1, "起始時間無效"))?;
+        // 結束時間後可能接 WebVTT 式的位置設定
+        let b = b.split_whitespace().next().unwrap_or("");
+        let end = parse_clock(b)
+            .ok_or_else(|| Error::parse(self.line, column(raw, "-->") + 3, "結束時間無效"))?;
+        Ok((start, end))
+    }
+
+    fn sbv_timing(&self, raw: &str) -> Result<(u64, u64)> {
+        let Some((a, b)) = raw.trim().split_once(',') else {
+            return Err(Error::parse(
+                self.line,
+                1,
+                "預期時間軸 `0:00:00.000,0:00:00.000`",
+            ));
+        };
+        let start = parse_clock(a).ok_or_else(|| Error::parse(self.line, 1, "起始時間無效"))?;
+        let end = parse_clock(b)
+            .ok_or_else(|| Error::parse(self.line, column(raw, ",") + 1, "結束時間無效"))?;
+        Ok((start, end))
+    }
+
+    // --- MicroDVD：一行一句，`{}` 結束影格顯示到下一句開始 ---
+
+    fn push_microdvd(&mut self, raw: &str, out: &mut Vec<Cue>) -> Result<()> {
+        let l = raw.trim();
+        if l.is_empty() {
+            return Ok(());
+        }
+        let fps = *self
+            .fps
+            .get_or_insert_with(|| header_fps(l).unwrap_or(DEFAULT_FPS));
+        let to_ms = |frame: u64| (frame as f64 * 1000.0 / fps).round() as u64;
+        let line = self.line;
+        let caps = MICRODVD
+            .captures(l)
+            .ok_or_else(|| Error::parse(line, 1, "預期 `{起始影格}{結束影格}文字`"))?;
+        let start: u64 = caps[1]
+            .parse()
+            .map_err(|_| Error::parse(line, 2, "起始影格無效"))?;
+        // 檔頭 {1}{1}23.976 只指定影格率
+        if start == 1 && &caps[2] == "1" && caps[3].trim().parse::<f64>().is_ok() {
+            return Ok(());
+        }
+        let end = match &caps[2] {
+            "" => None,
+            e => Some(
+                e.parse::<u64>()
+                    .map_err(|_| Error::parse(line, column(raw, "}{") + 2, "結束影格無效"))?,
+            ),
+        };
+        let cue = Cue {
+            line,
+            start_ms: to_ms(start),
+            end_ms: end.map(to_ms).unwrap_or(0),
+            text: microdvd_text(&caps[3]),
+        };
+        if let Some(mut prev) = self.open.take() {
+            prev.end_ms = cue.start_ms;
+            out.push(prev);
+        }
+        if end.is_some() {
+            out.push(cue);
+        } else {
+            self.open = Some(cue);
+        }
+        Ok(())
+    }
+
+    // --- SAMI：每個 SYNC 到下一個 SYNC 之間為一句，空白內容表示清除畫面 ---
+
+    fn push_sami(&mut self, raw: &str, out: &mut Vec<Cue>) -> Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        let mut line = raw;
+        if let Some(m) = SAMI_END.find(line) {
+            line = &line[..m.start()];
+            self.done = true;
+        }
+        let mut pos = 0;
+        for c in SAMI_SYNC.captures_iter(line) {
+            let tag = c.get(0).expect("整體比對");
+            if self.sync.is_some() {
+                self.content.push_str(&line[pos..tag.start()]);
+            }
+            let start: u64 = c[1]
+                .parse()
+                .map_err(|_| Error::parse(self.line, 1, "SYNC Start 無效"))?;
+            self.close_sync(out);
+            // 上一句顯示到這個 SYNC
+            if let Some(mut prev) = self.open.take() {
+                prev.end_ms = start;
+                out.push(prev);
+            }
+            self.sync = Some((self.line, start));
+            pos = tag.end();
+        }
+        if self.sync.is_some() {
+            self.content.push_str(&line[pos..]);
+            self.content.push('\n');
+        }
+        Ok(())
+    }
+
+    /// 結束目前 SYNC 的內容；有文字時成為待定結束時間的一句。
+    fn close_sync(&mut self, out: &mut Vec<Cue>) {
+        let Some((line, start)) = self.sync else {
+            return;
+        };
+        let text = sami_text(&self.content);
+        self.content.clear();
+        if text.is_empty() {
+            return;
+        }
+        if let Some(prev) = self.open.take() {
+            out.push(prev);
+        }
+        self.open = Some(Cue {
+            line,
+            start_ms: start,
+            end_ms: u64::MAX,
+            text,
+        });
+    }
+}
+
+// --- [ SRT ] ---
+
+fn render_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, c) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            clock(c.start_ms, ',', 2),
+            clock(c.end_ms, ',', 2),
+            c.text
+        ));
+    }
+    out
+}
+
+// --- [ SubViewer / SBV ] ---
+
+fn render_sbv(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for c in cues {
+        out.push_str(&format!(
+            "{},{}\n{}\n\n",
+            clock(c.start_ms, '.', 1),
+            clock(c.end_ms, '.', 1),
+            strip_tags(&c.text)
+        ));
+    }
+    out
+}
+
+// --- [ MicroDVD ] ---
+
+static MICRODVD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\{(\d+)\}\{(\d*)\}(.*)$").expect("內建樣式"));
+static MICRODVD_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{[A-Za-z]:[^}]*\}").expect("內建樣式"));
+
+/// `|` 為換行；`{y:i}` 只作用於該行、`{Y:i}` 作用於整句，其餘控制碼捨棄。
+fn microdvd_text(raw: &str) -> String {
+    let all_italic = raw.contains("{Y:i}");
+    raw.split('|')
+        .map(|part| {
+            let italic = all_italic || part.contains("{y:i}");
+            let plain = MICRODVD_CODE.replace_all(part, "");
+            if italic {
+                format!("<i>{}</i>", plain)
+            } else {
+                plain.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_microdvd(cues: &[Cue], fps: f64) -> String {
+    let to_frame = |ms: u64| (ms as f64 * fps / 1000.0).round() as u64;
+    let mut out = format!("{{1}}{{1}}{}\n", fps);
+    for c in cues {
+        let text = c
+            .text
+            .lines()
+            .map(|l| {
+                if l.contains("<i>") {
+                    format!("{{y:i}}{}", strip_tags(l))
+                } else {
+                    strip_tags(l)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        out.push_str(&format!(
+            "{{{}}}{{{}}}{}\n",
+            to_frame(c.start_ms),
+            to_frame(c.end_ms),
+            text
+        ));
+    }
+    out
+}
+
+// --- [ SAMI ] ---
+
+static SAMI_SYNC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<sync\s+start\s*=\s*["']?(\d+)["']?[^>]*>"#).expect("內建樣式")
+});
+static SAMI_BR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>").expect("內建樣式"));
+static SAMI_END: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)</body\s*>").expect("內建樣式"));
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").expect("內建樣式"));
+
+/// 去掉 `<P>`、`<FONT>` 等標籤，保留斜體；`&nbsp;` 單獨出現表示清除畫面。
+fn sami_text(raw: &str) -> String {
+    let with_breaks = SAMI_BR.replace_all(raw, "\n");
+    let kept = HTML_TAG.replace_all(&with_breaks, |c: &regex::Captures| {
+        match c[0].to_ascii_lowercase().as_str() {
+            "<i>" => "<i>".to_string(),
+            "</i>" => "</i>".to_string(),
+            _ => String::new(),
+        }
+    });
+    let decoded = kept
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+    decoded
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_sami(cues: &[Cue]) -> String {
+    let mut out = String::from(
+        "<SAMI>\n<HEAD>\n<TITLE></TITLE>\n<STYLE TYPE=\"text/css\">\n<!--\n\
+         P { margin-left:8pt; margin-right:8pt; margin-bottom:2pt; margin-top:2pt; \
+         text-align:center; font-size:20pt; font-family:Arial, sans-serif; \
+         font-weight:normal; color:white; }\n\
+         .ZHCC { Name:Chinese; lang:zh; SAMIType:CC; }\n-->\n</STYLE>\n</HEAD>\n<BODY>\n",
+    );
+    for (i, c) in cues.iter().enumerate() {
+        let escaped = c
+            .text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace("&lt;i&gt;", "<i>")
+            .replace("&lt;/i&gt;", "</i>")
+            .replace('\n', "<br>");
+        out.push_str(&format!(
+            "<SYNC Start={}><P Class=ZHCC>{}\n",
+            c.start_ms, escaped
+        ));
+        // 與下一句之間有空檔時才需要清除畫面
+        if cues.get(i + 1).is_none_or(|n| n.start_ms > c.end_ms) {
+            out.push_str(&format!("<SYNC Start={}><P Class=ZHCC>&nbsp;\n", c.end_ms));
+        }
+    }
+    out.push_str("</BODY>\n</SAMI>\n");
+    out
+}
+
+fn strip_tags(text: &str) -> String {
+    HTML_TAG.replace_all(text, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cues() -> Vec<Cue> {
+        vec![
+            Cue {
+                line: 0,
+                start_ms: 1_000,
+                end_ms: 2_500,
+                text: "第一句\n<i>第二行</i>".to_string(),
+            },
+            Cue {
+                line: 0,
+                start_ms: 3_000,
+                end_ms: 4_000,
+                text: "A & B".to_string(),
+            },
+        ]
+    }
+
+    /// 比較時間與文字，忽略來源行號
+    fn timing(cues: &[Cue]) -> Vec<(u64, u64, String)> {
+        cues.iter()
+            .map(|c| (c.start_ms, c.end_ms, c.text.clone()))
+            .collect()
+    }
+
+    fn parse_error_at(result: Result<Vec<Cue>>) -> (usize, usize) {
+        match result {
+            Err(Error::Parse { line, column, .. }) => (line, column),
+            other => panic!("預期格式錯誤: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn clock_parses_short_fractions_and_rejects_overflow() {
+        assert_eq!(parse_clock("00:01:02,5"), Some(62_500));
+        assert_eq!(parse_clock("1:00:00.001"), Some(3_600_001));
+        assert_eq!(parse_clock("00:05"), Some(5_000));
+        assert_eq!(parse_clock("00:60:00,000"), None);
+        assert_eq!(parse_clock("00:00:01,0000"), None);
+        assert_eq!(clock(3_723_004, ',', 2), "01:02:03,004");
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let text = render(&cues(), Format::Srt, DEFAULT_FPS).unwrap();
+        assert!(text.starts_with("1\n00:00:01,000 --> 00:00:02,500\n第一句\n"));
+        let back = parse(&text, Format::Srt, None).unwrap();
+        assert_eq!(timing(&back), timing(&cues()));
+        assert_eq!(back[1].line, 7);
+    }
+
+    #[test]
+    fn srt_errors_point_at_the_bad_field() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\n字\n\n2\n00:00:03,000 --> 0x:00:04\n";
+        assert_eq!(parse_error_at(parse(text, Format::Srt, None)), (6, 17));
+        assert_eq!(
+            parse_error_at(parse("1\n文字\n", Format::Srt, None)),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn srt_blank_lines_inside_a_cue_continue_it() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\n第一段\n\n第二段\n\n\
+                    2024\n\n\
+                    2\n00:00:03,000 --> 00:00:04,000\n下一句\n";
+        let cues = parse(text, Format::Srt, None).unwrap();
+        assert_eq!(
+            timing(&cues),
+            [
+                (1_000, 2_000, "第一段\n第二段\n2024".to_string()),
+                (3_000, 4_000, "下一句".to_string()),
+            ]
+        );
+        assert_eq!((cues[0].line, cues[1].line), (2, 10));
+        // 時間軸前的編號照常略過；延續後仍可回報下一個時間軸的錯誤
+        let bad = "1\n00:00:01,000 --> 00:00:02,000\n字\n\n續\n\n2\n00:00:03,000 --> x\n";
+        assert_eq!(parse_error_at(parse(bad, Format::Srt, None)), (8, 17));
+    }
+
+    #[test]
+    fn sbv_round_trip_drops_tags_and_reads_br() {
+        let text = render(&cues(), Format::Sbv, DEFAULT_FPS).unwrap();
+        assert!(text.starts_with("0:00:01.000,0:00:02.500\n第一句\n第二行\n"));
+        let back = parse(&text, Format::Sbv, None).unwrap();
+        assert_eq!(back[0].text, "第一句\n第二行");
+        assert_eq!(back[1].end_ms, 4_000);
+        let br = parse("0:00:01.000,0:00:02.000\n上[br]下\n", Format::Sbv, None).unwrap();
+        assert_eq!(br[0].text, "上\n下");
+        assert_eq!(
+            parse_error_at(parse("0:00:01.000;0:00:02.000\n", Format::Sbv, None)),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn microdvd_round_trip_keeps_italics_and_header_fps() {
+        let text = render(&cues(), Format::MicroDvd, 25.0).unwrap();
+        assert!(text.starts_with("{1}{1}25\n{25}{63}第一句|{y:i}第二行\n"));
+        assert_eq!(frame_rate(&text, None), 25.0);
+        let back = parse(&text, Format::MicroDvd, None).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back[0].text, "第一句\n<i>第二行</i>");
+        assert_eq!((back[1].start_ms, back[1].end_ms), (3_000, 4_000));
+    }
+
+    #[test]
+    fn microdvd_open_end_runs_to_next_cue() {
+        let back = parse("{0}{}甲\n{50}{}乙\n", Format::MicroDvd, Some(25.0)).unwrap();
+        assert_eq!(back[0].end_ms, 2_000);
+        assert_eq!(back[1].end_ms, 2_000 + LAST_CUE_MS);
+        assert_eq!(
+            parse_error_at(parse("{0}{10}甲\n文字\n", Format::MicroDvd, None)),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn sami_round_trip_escapes_text() {
+        let text = render(&cues(), Format::Sami, DEFAULT_FPS).unwrap();
+        assert!(text.contains("<SYNC Start=3000><P Class=ZHCC>A &amp; B\n"));
+        assert!(text.contains("第一句<br><i>第二行</i>"));
+        let back = parse(&text, Format::Sami, None).unwrap();
+        assert_eq!(timing(&back), timing(&cues()));
+    }
+
+    #[test]
+    fn sami_without_sync_is_an_error() {
+        let err = parse("<SAMI><BODY><P>字</BODY></SAMI>", Format::Sami, None);
+        assert_eq!(parse_error_at(err), (0, 0));
+    }
+
+    #[test]
+    fn unsupported_formats_are_errors() {
+        assert!(!supports(Format::Ass));
+        assert!(parse("", Format::Ass, None).is_err());
+        assert!(render(&cues(), Format::Lrc, DEFAULT_FPS).is_err());
+    }
+}
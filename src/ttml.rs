@@ -0,0 +1,565 @@
+// ==========================================
+// CW TTML Module
+// ==========================================
+//
+// TTML / DFXP / IMSC1 文字字幕。轉換時只改 `<body>` 內的文字節點，
+// 標籤、屬性、樣式、區域與 `<head>` 原樣保留 (字型名稱等屬性值不能被轉換)。
+// 另提供時間軸讀寫，供 `--to` 與其他格式互轉。
+
+use crate::error::{Error, Result};
+use crate::subtitle::Cue;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// 沒有 `ttp:frameRate` 時的預設影格率 (TTML 規範值)
+const DEFAULT_FRAME_RATE: f64 = 30.0;
+
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"&(#[0-9]+|#x[0-9A-Fa-f]+|[A-Za-z][A-Za-z0-9]*);").expect("內建樣式")
+});
+static ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([A-Za-z_][\w.:-]*)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("內建樣式")
+});
+static CLOCK_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d+):(\d{2}):(\d{2})(?:(\.\d+)|:(\d+)(?:\.\d+)?)?$").expect("內建樣式")
+});
+static OFFSET_TIME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d+(?:\.\d+)?)(h|ms|m|s|f|t)$").expect("內建樣式"));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    /// 標籤、註解、宣告等標記
+    Markup(&'a str),
+    Text(&'a str),
+}
+
+/// 切成標記與文字；CDATA 的內容視為文字。
+fn tokenize(doc: &str) -> Result<Vec<Token<'_>>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let line_at = |i: usize| doc[..i].matches('\n').count() + 1;
+    while pos < doc.len() {
+        let rest = &doc[pos..];
+        let Some(lt) = rest.find('<') else {
+            out.push(Token::Text(rest));
+            break;
+        };
+        if lt > 0 {
+            out.push(Token::Text(&rest[..lt]));
+        }
+        let start = pos + lt;
+        let tail = &doc[start..];
+        let unterminated = || Error::parse(line_at(start), 0, "標記未結束");
+        if let Some(body) = tail.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").ok_or_else(unterminated)?;
+            out.push(Token::Markup("<![CDATA["));
+            out.push(Token::Text(&body[..end]));
+            out.push(Token::Markup("]]>"));
+            pos = start + 9 + end + 3;
+            continue;
+        }
+        let len = if tail.starts_with("<!--") {
+            tail.find("-->").ok_or_else(unterminated)? + 3
+        } else if tail.starts_with("<?") {
+            tail.find("?>").ok_or_else(unterminated)? + 2
+        } else {
+            // 屬性值裡可能出現 `>`
+            let mut quote = None;
+            let end = tail.char_indices().skip(1).find(|&(_, c)| match quote {
+                Some(q) if c == q => {
+                    quote = None;
+                    false
+                }
+                Some(_) => false,
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    false
+                }
+                None => c == '>',
+            });
+            end.ok_or_else(unterminated)?.0 + 1
+        };
+        out.push(Token::Markup(&tail[..len]));
+        pos = start + len;
+    }
+    Ok(out)
+}
+
+/// 標籤的本地名稱 (去掉命名空間前綴) 與種類
+fn tag_info(markup: &str) -> Option<(&str, TagKind)> {
+    let inner = markup.strip_prefix('<')?.strip_suffix('>')?;
+    if inner.starts_with(['!', '?']) {
+        return None;
+    }
+    let (kind, inner) = match inner.strip_prefix('/') {
+        Some(i) => (TagKind::End, i),
+        None if inner.ends_with('/') => (TagKind::Empty, inner),
+        None => (TagKind::Start, inner),
+    };
+    let name = inner
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or("");
+    Some((name.rsplit(':').next().unwrap_or(name), kind))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TagKind {
+    Start,
+    End,
+    Empty,
+}
+
+fn attr<'a>(markup: &'a str, name: &str) -> Option<&'a str> {
+    ATTR.captures_iter(markup).find_map(|c| {
+        let key = c.get(1)?.as_str();
+        let local = key.rsplit(':').next().unwrap_or(key);
+        (key == name || local == name)
+            .then(|| c.get(2).or_else(|| c.get(3)).map(|m| m.as_str()))
+            .flatten()
+    })
+}
+
+/// 依序走訪 `<body>` 內 (不含 `<metadata>`) 的文字節點。
+fn for_each_body_text<'a>(tokens: &[Token<'a>], mut f: impl FnMut(usize, &'a str)) {
+    let mut in_body = false;
+    let mut skip = 0usize;
+    for (i, t) in tokens.iter().enumerate() {
+        match *t {
+            Token::Markup(m) => match tag_info(m) {
+                Some(("body", TagKind::Start)) => in_body = true,
+                Some(("body", TagKind::End)) => in_body = false,
+                Some(("metadata", TagKind::Start)) => skip += 1,
+                Some(("metadata", TagKind::End)) => skip = skip.saturating_sub(1),
+                _ => {}
+            },
+            Token::Text(text) if in_body && skip == 0 => f(i, text),
+            Token::Text(_) => {}
+        }
+    }
+}
+
+/// 只轉換 `<body>` 內的文字節點；實體參照 (`&amp;`、`&#x4E2D;`) 原樣保留。
+pub fn convert_text_nodes(doc: &str, mut convert: impl FnMut(&str) -> String) -> Result<String> {
+    let tokens = tokenize(doc)?;
+    let mut converted: Vec<Option<String>> = vec![None; tokens.len()];
+    for_each_body_text(&tokens, |i, text| {
+        if text.trim().is_empty() {
+            return;
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for m in ENTITY.find_iter(text) {
+            out.push_str(&convert(&text[last..m.start()]));
+            out.push_str(m.as_str());
+            last = m.end();
+        }
+        out.push_str(&convert(&text[last..]));
+        converted[i] = Some(out);
+    });
+    let mut out = String::with_capacity(doc.len());
+    for (t, c) in tokens.iter().zip(converted) {
+        match (t, c) {
+            (_, Some(c)) => out.push_str(&c),
+            (Token::Markup(s) | Token::Text(s), None) => out.push_str(s),
+        }
+    }
+    Ok(out)
+}
+
+fn unescape(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |c: &regex::Captures| {
+            let name = &c[1];
+            let code = if let Some(hex) = name.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(dec) = name.strip_prefix('#') {
+                dec.parse().ok()
+            } else {
+                None
+            };
+            match (code.and_then(char::from_u32), name) {
+                (Some(ch), _) => ch.to_string(),
+                (None, "amp") => "&".to_string(),
+                (None, "lt") => "<".to_string(),
+                (None, "gt") => ">".to_string(),
+                (None, "quot") => "\"".to_string(),
+                (None, "apos") => "'".to_string(),
+                (None, "nbsp") => "\u{a0}".to_string(),
+                _ => c[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// 文件層級的時間參數
+struct Timing {
+    frame_rate: f64,
+    tick_rate: f64,
+}
+
+/// 解析時間運算式：`01:02:03.456`、`01:02:03:12` (影格) 或 `12.5s`、`500ms`、`10f`、`100t`。
+fn parse_time(expr: &str, timing: &Timing) -> Option<u64> {
+    let expr = expr.trim();
+    let secs = if let Some(c) = CLOCK_TIME.captures(expr) {
+        let h: f64 = c[1].parse().ok()?;
+        let m: f64 = c[2].parse().ok()?;
+        let s: f64 = c[3].parse().ok()?;
+        let frac: f64 = match (c.get(4), c.get(5)) {
+            (Some(f), _) => format!("0{}", f.as_str()).parse().ok()?,
+            (None, Some(fr)) => fr.as_str().parse::<f64>().ok()? / timing.frame_rate,
+            _ => 0.0,
+        };
+        h * 3600.0 + m * 60.0 + s + frac
+    } else {
+        let c = OFFSET_TIME.captures(expr)?;
+        let n: f64 = c[1].parse().ok()?;
+        match &c[2] {
+            "h" => n * 3600.0,
+            "m" => n * 60.0,
+            "s" => n,
+            "ms" => n / 1000.0,
+            "f" => n / timing.frame_rate,
+            _ => n / timing.tick_rate,
+        }
+    };
+    Some((secs * 1000.0).round() as u64)
+}
+
+/// 讀出 `<p begin=… end=…>` 的時間軸；`<br/>` 為換行，斜體 `<span>` 轉為 `<i>`。
+pub fn parse(doc: &str) -> Result<Vec<Cue>> {
+    let tokens = tokenize(doc)?;
+    let root = tokens.iter().find_map(|t| match *t {
+        Token::Markup(m) if matches!(tag_info(m), Some(("tt", _))) => Some(m),
+        _ => None,
+    });
+    let Some(root) = root else {
+        return Err(Error::parse(0, 0, "找不到 <tt> 根元素"));
+    };
+    let frame_rate = attr(root, "frameRate")
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|f| *f > 0.0)
+        .unwrap_or(DEFAULT_FRAME_RATE);
+    let timing = Timing {
+        frame_rate,
+        tick_rate: attr(root, "tickRate")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|f| *f > 0.0)
+            .unwrap_or(1.0),
+    };
+
+    let mut cues = Vec::new();
+    let mut line = 1;
+    let mut current: Option<(Cue, Vec<bool>)> = None;
+    let mut depth = 0usize;
+    let mut in_body = false;
+    // `<body>`、`<div>` 的 begin 疊加為子元素時間的起點 (預設 timeContainer="par")
+    let mut offsets: Vec<u64> = Vec::new();
+    for t in &tokens {
+        let (s, is_markup) = match *t {
+            Token::Markup(s) => (s, true),
+            Token::Text(s) => (s, false),
+        };
+        let at = line;
+        line += s.matches('\n').count();
+        if !is_markup {
+            if let Some((cue, _)) = current.as_mut() {
+                let collapsed = unescape(s).split_whitespace().collect::<Vec<_>>().join(" ");
+                if !collapsed.is_empty() {
+                    // 相鄰文字節點之間原本的空白
+                    if s.starts_with(char::is_whitespace) && !cue.text.ends_with(['\n', ' ']) {
+                        cue.text.push(' ');
+                    }
+                    cue.text.push_str(&collapsed);
+                    if s.ends_with(char::is_whitespace) {
+                        cue.text.push(' ');
+                    }
+                }
+            }
+            continue;
+        }
+        let Some((name, kind)) = tag_info(s) else {
+            continue;
+        };
+        match (name, kind) {
+            ("body" | "div", TagKind::Start) => {
+                let base = offsets.last().copied().unwrap_or(0);
+                let begin = attr(s, "begin").and_then(|v| parse_time(v, &timing));
+                offsets.push(base + begin.unwrap_or(0));
+                in_body |= name == "body";
+            }
+            ("body" | "div", TagKind::End) => {
+                offsets.pop();
+                in_body &= name != "body";
+            }
+            ("p", TagKind::Start) if in_body && current.is_none() => {
+                let base = offsets.last().copied().unwrap_or(0);
+                let begin = attr(s, "begin").and_then(|v| parse_time(v, &timing));
+                let end = attr(s, "end").and_then(|v| parse_time(v, &timing));
+                let dur = attr(s, "dur").and_then(|v| parse_time(v, &timing));
+                let Some(begin) = begin else {
+                    return Err(Error::parse(at, 0, "<p> 缺少有效的 begin 時間"));
+                };
+                let Some(end) = end.or(dur.map(|d| begin + d)) else {
+                    return Err(Error::parse(at, 0, "<p> 缺少有效的 end 或 dur 時間"));
+                };
+                current = Some((
+                    Cue {
+                        line: at,
+                        start_ms: base + begin,
+                        end_ms: base + end,
+                        text: String::new(),
+                    },
+                    Vec::new(),
+                ));
+                depth = 0;
+            }
+            ("br", TagKind::Empty | TagKind::Start) => {
+                if let Some((cue, _)) = current.as_mut() {
+                    let trimmed = cue.text.trim_end_matches(' ').len();
+                    cue.text.truncate(trimmed);
+                    cue.text.push('\n');
+                }
+            }
+            ("span", TagKind::Start) => {
+                if let Some((cue, spans)) = current.as_mut() {
+                    let italic = attr(s, "fontStyle") == Some("italic");
+                    if italic {
+                        cue.text.push_str("<i>");
+                    }
+                    spans.push(italic);
+                }
+            }
+            ("span", TagKind::End) => {
+                if let Some((cue, spans)) = current.as_mut() {
+                    if spans.pop() == Some(true) {
+                        cue.text.push_str("</i>");
+                    }
+                }
+            }
+            ("p", TagKind::Start) => depth += 1,
+            ("p", TagKind::End) => {
+                if depth > 0 {
+                    depth -= 1;
+                } else if let Some((mut cue, _)) = current.take() {
+                    cue.text = cue
+                        .text
+                        .lines()
+                        .map(str::trim)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    cues.push(cue);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(cues)
+}
+
+fn clock(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// 輸出 IMSC1 文字設定檔 (text profile) 的 TTML；斜體以 `tts:fontStyle` 表示。
+pub fn render(cues: &[Cue]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <tt xmlns=\"http://www.w3.org/ns/ttml\" \
+         xmlns:tts=\"http://www.w3.org/ns/ttml#styling\" \
+         xmlns:ttp=\"http://www.w3.org/ns/ttml#parameter\" \
+         ttp:profile=\"http://www.w3.org/ns/ttml/profile/imsc1/text\" \
+         xml:lang=\"zh\">\n\
+         \x20 <head>\n\
+         \x20   <styling>\n\
+         \x20     <style xml:id=\"s0\" tts:textAlign=\"center\" tts:color=\"white\" \
+         tts:fontFamily=\"proportionalSansSerif\"/>\n\
+         \x20   </styling>\n\
+         \x20   <layout>\n\
+         \x20     <region xml:id=\"r0\" tts:origin=\"10% 80%\" tts:extent=\"80% 15%\" \
+         tts:displayAlign=\"after\"/>\n\
+         \x20   </layout>\n\
+         \x20 </head>\n\
+         \x20 <body style=\"s0\" region=\"r0\">\n\
+         \x20   <div>\n",
+    );
+    for c in cues {
+        let text = c
+            .text
+            .lines()
+            .map(|l| {
+                escape(l)
+                    .replace("&lt;i&gt;", "<span tts:fontStyle=\"italic\">")
+                    .replace("&lt;/i&gt;", "</span>")
+            })
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        out.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\">{}</p>\n",
+            clock(c.start_ms),
+            clock(c.end_ms),
+            text
+        ));
+    }
+    out.push_str("    </div>\n  </body>\n</tt>\n");
+    out
+}
+
+/// `<body>` 開始的行號 (1 起算)，預覽時略過檔頭
+pub fn body_line(doc: &str) -> usize {
+    let Ok(tokens) = tokenize(doc) else {
+        return 1;
+    };
+    let mut line = 1;
+    for t in tokens {
+        let (Token::Markup(s) | Token::Text(s)) = t;
+        if matches!(tag_info(s), Some(("body", TagKind::Start))) {
+            return line;
+        }
+        line += s.matches('\n').count();
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "<?xml version=\"1.0\"?>\n\
+        <tt xmlns=\"http://www.w3.org/ns/ttml\" ttp:frameRate=\"25\">\n\
+        <head><metadata><ttm:title>软件</ttm:title></metadata>\n\
+        <styling><style xml:id=\"s\" tts:fontFamily=\"软体\"/></styling></head>\n\
+        <body><div>\n\
+        <p begin=\"00:00:01.000\" end=\"00:00:02:12\" title=\"a > b\">软件 &amp; <span tts:fontStyle=\"italic\">硬件</span><br/>第二行</p>\n\
+        <p begin=\"3s\" dur=\"500ms\"><![CDATA[<软>]]></p>\n\
+        </div></body>\n</tt>\n";
+
+    fn upper(s: &str) -> String {
+        s.replace('软', "軟")
+    }
+
+    fn error_line(result: Result<Vec<Cue>>) -> usize {
+        match result {
+            Err(Error::Parse { line, .. }) => line,
+            other => panic!("預期格式錯誤: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tokenize_rejects_unterminated_markup() {
+        for doc in [
+            "<tt>\n<p begin=\"1s",
+            "<!-- 註解",
+            "<![CDATA[文字",
+            "<?xml ",
+        ] {
+            assert!(tokenize(doc).is_err(), "{}", doc);
+        }
+        // 屬性值內的 `>` 不會提早結束標籤
+        let tokens = tokenize("<p a=\"1>2\">字</p>").unwrap();
+        assert_eq!(tokens[0], Token::Markup("<p a=\"1>2\">"));
+        assert_eq!(tokens[1], Token::Text("字"));
+    }
+
+    #[test]
+    fn convert_touches_only_body_text() {
+        let out = convert_text_nodes(DOC, upper).unwrap();
+        // 中繼資料與屬性值不動，實體參照保留
+        assert!(out.contains("<ttm:title>软件</ttm:title>"));
+        assert!(out.contains("tts:fontFamily=\"软体\""));
+        assert!(out.contains("軟件 &amp; <span"));
+        assert!(out.contains("<![CDATA[<軟>]]>"));
+        assert_eq!(out.len(), DOC.len());
+    }
+
+    #[test]
+    fn parse_reads_timing_and_markup() {
+        let cues = parse(DOC).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start_ms, cues[0].end_ms), (1_000, 2_480));
+        assert_eq!(cues[0].text, "软件 & <i>硬件</i>\n第二行");
+        assert_eq!(cues[0].line, 6);
+        assert_eq!((cues[1].start_ms, cues[1].end_ms), (3_000, 3_500));
+        assert_eq!(cues[1].text, "<软>");
+    }
+
+    #[test]
+    fn div_and_body_begin_offset_their_cues() {
+        let doc = "<tt xmlns=\"http://www.w3.org/ns/ttml\">\n\
+            <body begin=\"1s\">\n\
+            <div begin=\"10s\">\n\
+            <p begin=\"2s\" end=\"3s\">甲</p>\n\
+            <div begin=\"00:01:00.000\"><p begin=\"0s\" dur=\"1s\">乙</p></div>\n\
+            </div>\n\
+            <div><p begin=\"4s\" end=\"5s\">丙</p></div>\n\
+            </body></tt>\n";
+        let times: Vec<_> = parse(doc)
+            .unwrap()
+            .iter()
+            .map(|c| (c.start_ms, c.end_ms, c.text.clone()))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (13_000, 14_000, "甲".to_string()),
+                (71_000, 72_000, "乙".to_string()),
+                // 離開 div 後只剩 body 的起點
+                (5_000, 6_000, "丙".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let cues = parse(DOC).unwrap();
+        let doc = render(&cues);
+        assert!(doc.contains("<p begin=\"00:00:03.000\" end=\"00:00:03.500\">&lt;软&gt;</p>"));
+        let back = parse(&doc).unwrap();
+        assert_eq!(back.len(), cues.len());
+        for (a, b) in back.iter().zip(&cues) {
+            assert_eq!(
+                (a.start_ms, a.end_ms, &a.text),
+                (b.start_ms, b.end_ms, &b.text)
+            );
+        }
+        assert_eq!(body_line(&doc), 11);
+    }
+
+    #[test]
+    fn parse_time_expressions() {
+        let timing = Timing {
+            frame_rate: 25.0,
+            tick_rate: 10_000.0,
+        };
+        assert_eq!(parse_time("01:02:03.5", &timing), Some(3_723_500));
+        assert_eq!(parse_time("00:00:01:05", &timing), Some(1_200));
+        assert_eq!(parse_time("1.5m", &timing), Some(90_000));
+        assert_eq!(parse_time("50f", &timing), Some(2_000));
+        assert_eq!(parse_time("25000t", &timing), Some(2_500));
+        assert_eq!(parse_time("1 s", &timing), None);
+    }
+
+    #[test]
+    fn missing_times_and_root_are_errors() {
+        let doc = "<tt>\n<body>\n<p end=\"1s\">字</p></body></tt>";
+        assert_eq!(error_line(parse(doc)), 3);
+        let doc = "<tt>\n<body>\n\n<p begin=\"1s\">字</p></body></tt>";
+        assert_eq!(error_line(parse(doc)), 4);
+        assert_eq!(
+            error_line(parse("<body><p begin=\"1s\" end=\"2s\"/></body>")),
+            0
+        );
+    }
+}
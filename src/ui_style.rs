@@ -1,5 +1,7 @@
 use colored::Colorize;
+use cw::core::{Config, ConfigDiagnostic, ConfigSource, CONFIG_KEYS};
 use cw::report_format::{FileReport, ResultStatus, SubtitleIssue};
+use std::path::PathBuf;
 
 pub fn status_info() -> String {
     "[ INFO ]".green().to_string()
@@ -8,12 +10,8 @@ pub fn status_fixd() -> String {
     "[ FIXD ]".yellow().bold().to_string()
 }
 
-pub fn print_help() {
-    println!("\n\x1b[1;36m🚀 CW 字幕工作站 v1.9.3\x1b[0m");
-    println!("============================================================");
-    println!("用法: cw <檔案.srt> [-p 專業] [-d 覆寫] [-b 傳送]");
-    println!("系統: --init (生成預設 cw.cfg)");
-}
+/// 預覽最多顯示的行數；轉換時也只保留這麼多行對照。
+pub const PREVIEW_LINES: usize = 15;
 
 pub fn print_translated_preview(
     pairs: &[(usize, String, String)],
@@ -21,7 +19,7 @@ pub fn print_translated_preview(
     issues: &[SubtitleIssue],
 ) {
     println!("{}", "--- 翻譯對照預覽 ---".dimmed());
-    for (n, o, t) in pairs.iter().take(15) {
+    for (n, o, t) in pairs.iter().take(PREVIEW_LINES) {
         let has_err = issues.iter().any(|i| i.line == *n);
         if full || o.trim() != t.trim() || has_err {
             let label = if has_err {
@@ -52,7 +50,7 @@ pub fn print_summary(reports: &[FileReport], dur: std::time::Duration) {
             icon,
             r.input_name,
             r.output_name,
-            r.translated_pairs.len(),
+            r.changed_lines,
             r.issues.len(),
             r.duration
         );
@@ -105,3 +103,64 @@ pub fn format_to_width(s: &str, w: usize) -> String {
         s.to_string() + &" ".repeat(w - cur_w)
     }
 }
+
+pub fn print_config(config: &Config) {
+    println!("\n⚙️  生效設定\n{}", "=".repeat(60));
+    if !config.profiles.is_empty() {
+        println!(
+            "{}",
+            format!("# 可用 profile: {}", config.profiles.join(", ")).dimmed()
+        );
+    }
+    let known = CONFIG_KEYS.iter().map(|(k, _, _)| *k);
+    let extra = config
+        .entries
+        .keys()
+        .map(String::as_str)
+        .filter(|k| !CONFIG_KEYS.iter().any(|(d, _, _)| d == k));
+    for k in known.chain(extra) {
+        let Some(e) = config.entries.get(k) else {
+            continue;
+        };
+        let v = if k.contains("password") && !e.value.is_empty() {
+            "******".to_string()
+        } else {
+            format!("\"{}\"", e.value)
+        };
+        println!(
+            "{} = {} {}",
+            format_to_width(k, 22),
+            v,
+            format!("# {}", e.source).dimmed()
+        );
+    }
+}
+
+/// 設定問題一律輸出到 stderr，避免污染管道模式的 stdout。
+pub fn print_config_diagnostics(diags: &[ConfigDiagnostic]) {
+    for d in diags {
+        if d.is_error() {
+            eprintln!("{} {}", "[ ERR  ]".red().bold(), d);
+        } else {
+            eprintln!("{} {}", "[ WARN ]".yellow(), d);
+        }
+    }
+}
+
+pub fn print_config_check(files: &[(PathBuf, ConfigSource)], config: &Config) {
+    println!("\n🩺 設定檢查\n{}", "=".repeat(60));
+    if files.is_empty() {
+        println!("  (未找到任何 cw.cfg，僅使用內建預設)");
+    }
+    for (_, src) in files {
+        println!("  已載入: {}", src);
+    }
+    print_config_diagnostics(&config.diagnostics);
+    let errs = config.diagnostics.iter().filter(|d| d.is_error()).count();
+    let warns = config.diagnostics.len() - errs;
+    if errs == 0 {
+        print_check_ok(&format!("設定有效 (警告 {} 則)", warns));
+    } else {
+        print_check_err(&format!("錯誤 {} 則 | 警告 {} 則", errs, warns));
+    }
+}
@@ -0,0 +1,255 @@
+// 以實際執行檔驗證命令列行為。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SRT: &str = "1\n00:00:01,000 --> 00:00:02,000\n软件\n\n";
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cw_cli_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, text: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, text).unwrap();
+}
+
+/// 在 `dir` 下執行 cw，回傳結束代碼。
+/// 清空環境變數、使用者設定目錄指向 `dir/xdg`，專案設定不往 `dir` 之外尋找，
+/// 結果不受本機設定影響。
+fn cw(dir: &Path, args: &[&str]) -> i32 {
+    cw_output(dir, args).0
+}
+
+/// 同 `cw`，另回傳標準輸出
+fn cw_output(dir: &Path, args: &[&str]) -> (i32, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_cw"))
+        .args(args)
+        .current_dir(dir)
+        .env_clear()
+        .env("XDG_CONFIG_HOME", dir.join("xdg"))
+        .env("CW_CONFIG_CEILING", dir)
+        .env("CW_LOG_DIRECTORY", dir.join("logs"))
+        .output()
+        .unwrap();
+    (
+        out.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&out.stdout).into_owned(),
+    )
+}
+
+/// `root` 下所有檔案的相對路徑 (排序)，略過日誌
+fn files(root: &Path) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for e in fs::read_dir(&dir).unwrap() {
+            let p = e.unwrap().path();
+            if p.is_dir() {
+                stack.push(p);
+            } else {
+                let rel = p
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                if !rel.starts_with("logs/") {
+                    out.push(rel);
+                }
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
+#[test]
+fn name_template_outputs_are_not_converted_again() {
+    let dir = scratch("template");
+    write(&dir.join("season/e1.srt"), SRT);
+    write(&dir.join("season/s1/e2.srt"), SRT);
+    for _ in 0..2 {
+        assert_eq!(
+            cw(
+                &dir,
+                &["convert", "season", "-r", "--name", "{stem}.zh-Hant.{ext}"]
+            ),
+            0
+        );
+    }
+    assert_eq!(
+        files(&dir),
+        [
+            "season/e1.srt",
+            "season/e1.zh-Hant.srt",
+            "season/s1/e2.srt",
+            "season/s1/e2.zh-Hant.srt",
+        ]
+    );
+    // 再輸出到其他目錄：上次的成果不列入
+    for _ in 0..2 {
+        let args = [
+            "convert",
+            "season",
+            "-r",
+            "-o",
+            "out",
+            "--name",
+            "{stem}.zh-Hant.{ext}",
+        ];
+        assert_eq!(cw(&dir, &args), 0);
+    }
+    assert_eq!(
+        files(&dir.join("out")),
+        ["e1.zh-Hant.srt", "s1/e2.zh-Hant.srt"]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn output_dir_inside_scan_is_excluded() {
+    let dir = scratch("outdir");
+    write(&dir.join("season/e1.srt"), SRT);
+    for _ in 0..2 {
+        assert_eq!(
+            cw(&dir, &["convert", "season", "-r", "-o", "season/out"]),
+            0
+        );
+    }
+    assert_eq!(files(&dir), ["season/e1.srt", "season/out/e1.srt.txt"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn audit_records_the_encoding_used() {
+    let dir = scratch("audit");
+    write(
+        &dir.join("e1.srt"),
+        "1\n00:00:01,000 --> 00:00:02,000\nsoftware\n\n",
+    );
+    let args = [
+        "convert",
+        "e1.srt",
+        "--input-encoding",
+        "gbk",
+        "--set",
+        "audit_log=audit.jsonl",
+    ];
+    assert_eq!(cw(&dir, &args), 0);
+    let audit = fs::read_to_string(dir.join("audit.jsonl")).unwrap();
+    assert!(audit.contains(r#""encoding":"GBK""#), "{}", audit);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fix_honours_configured_input_encoding() {
+    let dir = scratch("fix_encoding");
+    let utf16 =
+        |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    // 無 BOM 的 UTF-16LE 需靠設定指定編碼
+    fs::write(
+        dir.join("e1.srt"),
+        utf16("1\n00:00:01,000 --> 00:00:02,000\n软件\n"),
+    )
+    .unwrap();
+    let args = ["fix", "e1.srt", "--set", "input_encoding=utf-16le"];
+    assert_eq!(cw(&dir, &args), 0);
+    assert_eq!(
+        fs::read(dir.join("e1.srt")).unwrap(),
+        utf16("1\n00:00:01,000 --> 00:00:02,000\n软件\n\n")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn to_uses_target_extension() {
+    let dir = scratch("to_ext");
+    write(&dir.join("season/e1.srt"), SRT);
+    for _ in 0..2 {
+        assert_eq!(cw(&dir, &["convert", "season", "--to", "sbv"]), 0);
+    }
+    assert_eq!(files(&dir), ["season/e1.sbv", "season/e1.srt"]);
+    // 自訂模板的 {ext} 同樣改用目標格式
+    let args = [
+        "convert",
+        "season/e1.srt",
+        "--to",
+        "sbv",
+        "--name",
+        "{stem}.zh.{ext}",
+    ];
+    assert_eq!(cw(&dir, &args), 0);
+    assert!(dir.join("season/e1.zh.sbv").is_file());
+    // 輸入已是目標格式時沿用模板，不覆寫輸入檔
+    assert_eq!(cw(&dir, &["convert", "season/e1.sbv", "--to", "sbv"]), 0);
+    assert!(dir.join("season/e1.sbv.txt").is_file());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exit_codes_follow_severity() {
+    let dir = scratch("exit_codes");
+    write(
+        &dir.join("bad.srt"),
+        "1\n00:00:05,000 --> 00:00:02,000\n软件\n\n",
+    );
+    write(&dir.join("blocker"), "x");
+    assert_eq!(cw(&dir, &["convert", "bad.srt"]), 1);
+    // --strict 時警告歸為轉換失敗
+    assert_eq!(cw(&dir, &["convert", "bad.srt", "--strict"]), 3);
+    assert_eq!(cw(&dir, &["convert", "missing.srt"]), 4);
+    // 輸出目錄無法建立
+    assert_eq!(cw(&dir, &["convert", "bad.srt", "-o", "blocker/out"]), 4);
+    assert_eq!(cw(&dir, &["convert", "bad.srt", "--set", "no_such_key"]), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parallel_jobs_match_serial_output() {
+    let dir = scratch("jobs");
+    for i in 0..12 {
+        // 長短不一，讓各工作執行緒完成的順序與輸入順序不同
+        let cues: String = (0..(12 - i) * 40)
+            .map(|n| {
+                format!(
+                    "{}\n00:00:01,000 --> 00:00:02,000\n软件 {} {}\n\n",
+                    n + 1,
+                    i,
+                    n
+                )
+            })
+            .collect();
+        write(&dir.join(format!("season/s{}/e{:02}.srt", i % 3, i)), &cues);
+    }
+    // 報告明細只取狀態行，耗時等每次不同的內容不比較
+    let run = |jobs: &str, out: &str| {
+        let (code, stdout) = cw_output(&dir, &["convert", "season", "-r", "-j", jobs, "-o", out]);
+        assert_eq!(code, 0, "{}", stdout);
+        let report: Vec<String> = stdout
+            .lines()
+            .filter(|l| l.starts_with("[OK]"))
+            .map(|l| l.replace(out, "OUT"))
+            .collect();
+        assert_eq!(report.len(), 12, "{}", stdout);
+        report
+    };
+    let serial = run("1", "serial");
+    let parallel = run("4", "parallel");
+    assert_eq!(serial, parallel);
+    let outputs = files(&dir.join("serial"));
+    assert_eq!(outputs, files(&dir.join("parallel")));
+    assert_eq!(outputs.len(), 12);
+    for rel in &outputs {
+        assert_eq!(
+            fs::read(dir.join("serial").join(rel)).unwrap(),
+            fs::read(dir.join("parallel").join(rel)).unwrap(),
+            "{}",
+            rel
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
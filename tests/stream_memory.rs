@@ -0,0 +1,193 @@
+// 串流轉換的記憶體上限：逐行處理的格式不論輸入多大，配置的記憶體都不隨檔案成長。
+
+use cw::core::{Bom, Collect, Converter, LineEnding, StreamOptions};
+use cw::encoding::OutputEncoding;
+use cw::format::Format;
+use opencc_rust::DefaultConfig;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 記錄目前與最高的配置量
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let now = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(now, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+/// 產生第 n 個單元 (一句字幕或一行)
+type Unit = fn(usize) -> String;
+
+/// 即時產生的輸入：檔頭之後重複 `count` 個單元，不預先放進記憶體
+struct Generated {
+    header: String,
+    unit: Unit,
+    count: usize,
+    next: usize,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Generated {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            if !self.header.is_empty() {
+                self.buf = std::mem::take(&mut self.header).into_bytes();
+            } else if self.next < self.count {
+                self.buf = (self.unit)(self.next).into_bytes();
+                self.next += 1;
+            } else {
+                return Ok(0);
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// 每行的填充文字；行長一些，測試不必處理太多行
+const FILLER: &str =
+    "the quick brown fox jumps over the lazy dog, the quick brown fox jumps over the lazy dog, \
+the quick brown fox jumps over the lazy dog, the quick brown fox jumps over the lazy dog";
+
+fn srt(i: usize) -> String {
+    let (s, e) = (i as u64 * 2, i as u64 * 2 + 1);
+    format!(
+        "{}\n{:02}:{:02}:{:02},000 --> {:02}:{:02}:{:02},000\n软件 {} {}\n\n",
+        i + 1,
+        s / 3600,
+        s / 60 % 60,
+        s % 60,
+        e / 3600,
+        e / 60 % 60,
+        e % 60,
+        i,
+        FILLER
+    )
+}
+
+fn sbv(i: usize) -> String {
+    let (s, e) = (i as u64 * 2, i as u64 * 2 + 1);
+    format!(
+        "{}:{:02}:{:02}.000,{}:{:02}:{:02}.000\n软件 {} {}\n\n",
+        s / 3600,
+        s / 60 % 60,
+        s % 60,
+        e / 3600,
+        e / 60 % 60,
+        e % 60,
+        i,
+        FILLER
+    )
+}
+
+fn microdvd(i: usize) -> String {
+    format!("{{{}}}{{{}}}软件 {} {}\n", i * 50, i * 50 + 25, i, FILLER)
+}
+
+fn sami(i: usize) -> String {
+    format!(
+        "<SYNC Start={}><P Class=CHCC>软件 {} {}\n",
+        i * 2000,
+        i,
+        FILLER
+    )
+}
+
+fn ass(i: usize) -> String {
+    format!(
+        "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,软件 {} {}\n",
+        i, FILLER
+    )
+}
+
+fn lrc(i: usize) -> String {
+    format!(
+        "[{:02}:{:02}.00]软件 {} {}\n",
+        i / 60 % 100,
+        i % 60,
+        i,
+        FILLER
+    )
+}
+
+fn text(i: usize) -> String {
+    format!("软件 {} {}\n", i, FILLER)
+}
+
+/// 約 4 MiB 的輸入
+const BYTES: usize = 4 * 1024 * 1024;
+/// 逐行處理時容許的最高配置量 (遠小於輸入)
+const LIMIT: usize = 1024 * 1024;
+
+#[test]
+fn line_formats_stay_memory_bounded() {
+    let converter = Converter::new(DefaultConfig::S2T).unwrap();
+    let cases: [(Format, &str, Unit); 9] = [
+        (Format::Srt, "", srt),
+        (Format::Sbv, "", sbv),
+        (Format::MicroDvd, "", microdvd),
+        (Format::Sami, "<SAMI>\n<BODY>\n", sami),
+        (Format::Ass, "[Script Info]\n\n[Events]\n", ass),
+        (Format::WebVtt, "WEBVTT\n\n", srt),
+        (Format::Lrc, "", lrc),
+        (Format::Plain, "", text),
+        (Format::Markdown, "", text),
+    ];
+    for (format, header, unit) in cases {
+        let count = BYTES / unit(0).len();
+        let input = Generated {
+            header: header.to_string(),
+            unit,
+            count,
+            next: 0,
+            buf: Vec::new(),
+            pos: 0,
+        };
+        let opts = StreamOptions {
+            fix_trailing_newline: true,
+            diagnose: true,
+            collect: Collect::None,
+            line_ending: LineEnding::Preserve,
+            bom: Bom::Preserve,
+            input_encoding: None,
+            output_encoding: OutputEncoding::Utf8,
+            repair_mojibake: true,
+            format: Some(format),
+            output_format: None,
+            frame_rate: None,
+            lrc_metadata: false,
+        };
+        let base = CURRENT.load(Ordering::SeqCst);
+        PEAK.store(base, Ordering::SeqCst);
+        let summary = converter.convert_stream(input, io::sink(), &opts).unwrap();
+        let peak = PEAK.load(Ordering::SeqCst) - base;
+        assert!(summary.lines >= count, "{}: {} 行", format, summary.lines);
+        assert!(
+            peak < LIMIT,
+            "{} 轉換 {} 位元組時最高配置 {} 位元組",
+            format,
+            BYTES,
+            peak
+        );
+    }
+}